@@ -0,0 +1,91 @@
+use std::sync::{Arc, RwLock};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use itertools::Itertools;
+use noise::Perlin;
+
+use game::{
+    block::{dirt::render_dirt, Block},
+    block_registry::BlockRegistry,
+    level::{compress_chunk, decompress_chunk, generate_chunk, ChunkCompression},
+    position::ChunkPos,
+};
+
+/// Serialized chunks from a patch of generated terrain, spanning solid, surface and empty chunks.
+fn terrain() -> Vec<Vec<u8>> {
+    let mut registry = BlockRegistry::default();
+    registry.register(
+        "dirt".to_string(),
+        Block {
+            render: render_dirt,
+        },
+    );
+    let registry = Arc::new(RwLock::new(registry));
+
+    (-8..8)
+        .cartesian_product(-1..2)
+        .cartesian_product(-8..8)
+        .map(|((x, y), z)| {
+            let chunk = generate_chunk(
+                Perlin::default(),
+                ChunkPos::new(x, y, z),
+                Arc::clone(&registry),
+            );
+            chunk.serialize(&registry.read().unwrap())
+        })
+        .collect()
+}
+
+fn chunk_compression(c: &mut Criterion) {
+    let chunks = terrain();
+    let raw: usize = chunks.iter().map(Vec::len).sum();
+
+    println!("{} chunks, {raw} bytes uncompressed", chunks.len());
+
+    for compression in ChunkCompression::ALL {
+        let size: usize = chunks
+            .iter()
+            .map(|bytes| compress_chunk(bytes, compression).len())
+            .sum();
+        println!(
+            "{compression:?}: {size} bytes ({:.1}%)",
+            size as f64 / raw as f64 * 100.0
+        );
+    }
+
+    let mut group = c.benchmark_group("chunk_compression");
+
+    for compression in ChunkCompression::ALL {
+        let blobs = chunks
+            .iter()
+            .map(|bytes| compress_chunk(bytes, compression))
+            .collect_vec();
+
+        group.bench_function(
+            BenchmarkId::new("encode", format!("{compression:?}")),
+            |b| {
+                b.iter(|| {
+                    for bytes in &chunks {
+                        compress_chunk(bytes, compression);
+                    }
+                })
+            },
+        );
+
+        group.bench_function(
+            BenchmarkId::new("decode", format!("{compression:?}")),
+            |b| {
+                b.iter(|| {
+                    for blob in &blobs {
+                        decompress_chunk(blob).unwrap();
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, chunk_compression);
+criterion_main!(benches);
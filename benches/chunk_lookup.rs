@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use noise::Perlin;
+
+use game::{
+    block_registry::SharedBlockRegistry,
+    config::Config,
+    level::{
+        chunk_offsets, ChunkEntities, ChunkLoader, Level, LevelGenPlugin, MemoryStorage,
+        RenderDistance,
+    },
+};
+
+/// A headless app standing still with every chunk within `render_distance` already loaded, so
+/// a frame does nothing but check which chunks to load and unload.
+fn loaded_app(render_distance: i32) -> App {
+    let config = Config {
+        render_distance,
+        ..default()
+    };
+    let distance = RenderDistance::from(&config);
+    let mut app = App::new();
+
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(LevelGenPlugin)
+        .init_resource::<SharedBlockRegistry>()
+        .insert_resource(config)
+        .insert_resource(Level::new(
+            Arc::new(MemoryStorage::default()),
+            Perlin::default(),
+        ));
+
+    app.world.spawn((TransformBundle::default(), ChunkLoader));
+
+    // Chunks are only marked as loaded, since generating them isn't what is measured.
+    app.world
+        .resource_scope(|world, mut entities: Mut<ChunkEntities>| {
+            for pos in chunk_offsets(distance) {
+                let entity = world.spawn(pos).id();
+                entities.insert(pos, entity);
+            }
+        });
+
+    app.update();
+    app
+}
+
+fn chunk_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk_lookup");
+
+    for render_distance in [4, 8, 16] {
+        let mut app = loaded_app(render_distance);
+        let loaded = app.world.resource::<ChunkEntities>().len();
+
+        group.bench_function(
+            BenchmarkId::new("frame", format!("{render_distance} ({loaded} chunks)")),
+            |b| b.iter(|| app.update()),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, chunk_lookup);
+criterion_main!(benches);
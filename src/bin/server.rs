@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use bevy::{
+    app::ScheduleRunnerPlugin, asset::AssetPlugin, log::LogPlugin, prelude::*, scene::ScenePlugin,
+};
+use bevy_rapier3d::prelude::*;
+
+use game::{
+    block_registry::SharedBlockRegistry,
+    config::ConfigPlugin,
+    level::{ChunkLoader, LevelGenPlugin},
+    net::ServerPlugin,
+    register_blocks, setup_level, GRAVITY,
+};
+
+/// How often the headless server ticks its world.
+const TICK_RATE: f64 = 60.0;
+
+fn main() {
+    App::new()
+        .init_resource::<SharedBlockRegistry>()
+        .insert_resource(RapierConfiguration {
+            gravity: GRAVITY,
+            ..default()
+        })
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1.0 / TICK_RATE,
+            ))),
+        )
+        .add_plugins((
+            LogPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+            ScenePlugin,
+        ))
+        // Rapier looks up meshes for its async colliders, even though none are used here.
+        .add_asset::<Mesh>()
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .add_plugins(ConfigPlugin)
+        .add_plugins(LevelGenPlugin)
+        .add_plugins(ServerPlugin)
+        .add_systems(Startup, (setup_level, register_blocks, setup_spawn_loader))
+        .run();
+}
+
+/// Keeps the spawn area loaded so it is ready before anyone joins.
+fn setup_spawn_loader(mut commands: Commands) {
+    commands
+        .spawn(ChunkLoader)
+        .insert(TransformBundle::from(Transform::from_xyz(0.0, 20.0, 0.0)));
+}
@@ -0,0 +1,297 @@
+use std::{env, error::Error, fs, path::Path, process, sync::Arc};
+
+use bevy::utils::{HashMap, HashSet};
+use image::GrayImage;
+use noise::Perlin;
+
+use game::{
+    level::{
+        compress_chunk, decode_palette, decompress_chunk, remap_palette, terrain_height,
+        ChunkCompression, ChunkStorage, RegionStorage, SqliteStorage, StorageWrite, CHUNK_SIZE,
+        CHUNK_VOLUME,
+    },
+    position::ChunkPos,
+};
+
+const USAGE: &str = "\
+Usage: world <save> <command>
+
+<save> is a SQLite world file, or a directory of region files.
+The game must not be running on the same save.
+
+Commands:
+    stats                       Count the saved chunks and their sizes
+    vacuum                      Reclaim space left by rewritten and deleted chunks
+    reencode [compression]      Rewrite every chunk in the latest format
+    remap <from> <to>           Rename a block in every chunk
+    delete-block <name>         Replace a block with air in every chunk
+    prune <radius>              Delete chunks further than <radius> chunks from the origin
+    heightmap <png> [radius]    Export the terrain height around the origin";
+
+/// Writes are grouped so large worlds aren't rewritten one transaction per chunk.
+const BATCH_SIZE: usize = 256;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let [save, command @ ..] = &args[..] else {
+        usage();
+    };
+
+    if let Err(err) = run(save, command) {
+        eprintln!("error: {err}");
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    process::exit(2);
+}
+
+fn run(save: &str, command: &[&str]) -> Result<()> {
+    let path = Path::new(save);
+
+    if !path.exists() {
+        return Err(format!("no world at {save}").into());
+    }
+
+    let storage: Box<dyn ChunkStorage> = if path.is_dir() {
+        Box::new(RegionStorage::open(path)?)
+    } else {
+        Box::new(SqliteStorage::open(path)?)
+    };
+
+    match command {
+        ["stats"] => stats(&*storage),
+        ["vacuum"] => {
+            let before = disk_size(path)?;
+            storage.compact()?;
+            println!("{before} bytes before, {} bytes after", disk_size(path)?);
+            Ok(())
+        }
+        ["reencode"] => reencode(&*storage, ChunkCompression::default()),
+        ["reencode", compression] => {
+            let Some(compression) = ChunkCompression::ALL
+                .into_iter()
+                .find(|c| format!("{c:?}") == *compression)
+            else {
+                return Err(format!("unknown compression {compression}").into());
+            };
+            reencode(&*storage, compression)
+        }
+        ["remap", from, to] => rewrite(&*storage, |name| {
+            Some(if name == *from { to } else { name }.to_string())
+        }),
+        ["delete-block", block] => {
+            rewrite(&*storage, |name| (name != *block).then(|| name.to_string()))
+        }
+        ["prune", radius] => prune(&*storage, radius.parse()?),
+        ["heightmap", output] => heightmap(&*storage, output, 8),
+        ["heightmap", output, radius] => heightmap(&*storage, output, radius.parse()?),
+        _ => usage(),
+    }
+}
+
+/// The bytes a save takes on disk, including SQLite's write-ahead log.
+fn disk_size(path: &Path) -> Result<u64> {
+    if path.is_dir() {
+        let mut size = 0;
+
+        for entry in fs::read_dir(path)? {
+            size += disk_size(&entry?.path())?;
+        }
+
+        return Ok(size);
+    }
+
+    let mut size = fs::metadata(path)?.len();
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+
+    if let Ok(metadata) = fs::metadata(wal) {
+        size += metadata.len();
+    }
+
+    Ok(size)
+}
+
+fn load(storage: &dyn ChunkStorage, pos: ChunkPos) -> Result<Vec<u8>> {
+    storage
+        .load_chunk(pos)?
+        .ok_or_else(|| format!("chunk {pos:?} disappeared").into())
+}
+
+fn stats(storage: &dyn ChunkStorage) -> Result<()> {
+    let positions = storage.chunk_positions()?;
+    let mut sizes = Vec::with_capacity(positions.len());
+    let mut codecs = HashMap::<_, usize>::new();
+
+    for &pos in &positions {
+        let blob = load(storage, pos)?;
+        sizes.push(blob.len());
+        *codecs.entry(ChunkCompression::of(&blob)).or_default() += 1;
+    }
+
+    let total = sizes.iter().sum::<usize>();
+    let average = total.checked_div(sizes.len()).unwrap_or_default();
+    let largest = sizes.iter().max().copied().unwrap_or_default();
+
+    println!(
+        "{} chunks, {total} bytes ({average} average, {largest} largest)",
+        positions.len()
+    );
+
+    for (codec, count) in codecs {
+        match codec {
+            Some(codec) => println!("    {codec:?}: {count}"),
+            None => println!("    uncompressed (legacy): {count}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Passes every chunk through `f` and saves the ones it changed.
+fn update_chunks(
+    storage: &dyn ChunkStorage,
+    f: impl Fn(&[u8]) -> Result<Vec<u8>>,
+) -> Result<usize> {
+    let mut batch = Vec::new();
+    let mut changed = 0;
+
+    for pos in storage.chunk_positions()? {
+        let blob = load(storage, pos)?;
+        let data = f(&blob).map_err(|err| format!("chunk {pos:?}: {err}"))?;
+
+        if data != blob {
+            batch.push(StorageWrite::Chunk {
+                pos,
+                data: Arc::new(data),
+            });
+            changed += 1;
+        }
+
+        if batch.len() == BATCH_SIZE {
+            storage.save(&batch)?;
+            batch.clear();
+        }
+    }
+
+    storage.save(&batch)?;
+    Ok(changed)
+}
+
+fn reencode(storage: &dyn ChunkStorage, compression: ChunkCompression) -> Result<()> {
+    let changed = update_chunks(storage, |blob| {
+        let bytes = remap_palette(&decompress_chunk(blob)?, CHUNK_VOLUME, |name| {
+            Some(name.to_string())
+        })?;
+        Ok(compress_chunk(&bytes, compression))
+    })?;
+
+    println!("Re-encoded {changed} chunks as {compression:?}");
+    Ok(())
+}
+
+/// Renames blocks through `map`, keeping each chunk's compression.
+fn rewrite(storage: &dyn ChunkStorage, map: impl Fn(&str) -> Option<String>) -> Result<()> {
+    let changed = update_chunks(storage, |blob| {
+        let bytes = decompress_chunk(blob)?;
+        let remapped = remap_palette(&bytes, CHUNK_VOLUME, &map)?;
+
+        if remapped == bytes {
+            return Ok(blob.to_vec());
+        }
+
+        let compression = ChunkCompression::of(blob).unwrap_or_default();
+        Ok(compress_chunk(&remapped, compression))
+    })?;
+
+    println!("Updated {changed} chunks");
+    Ok(())
+}
+
+fn prune(storage: &dyn ChunkStorage, radius: f32) -> Result<()> {
+    let outside = storage
+        .chunk_positions()?
+        .into_iter()
+        .filter(|pos| ((pos.x * pos.x + pos.z * pos.z) as f32).sqrt() > radius)
+        .collect::<Vec<_>>();
+
+    storage.delete_chunks(&outside)?;
+    println!("Deleted {} chunks", outside.len());
+    Ok(())
+}
+
+/// Draws the highest block of every column within `radius` chunks of the origin, brighter for
+/// higher. Columns without saved chunks show the generated terrain.
+fn heightmap(storage: &dyn ChunkStorage, output: &str, radius: i32) -> Result<()> {
+    let size = CHUNK_SIZE as i32;
+    let noise = match storage.load_metadata("seed")? {
+        Some(seed) => Perlin::new(u32::from_be_bytes(
+            seed.try_into().map_err(|_| "corrupt seed")?,
+        )),
+        None => Perlin::default(),
+    };
+
+    let in_range = |pos: &ChunkPos| pos.x.abs() <= radius && pos.z.abs() <= radius;
+    let mut saved_levels = HashMap::<(i32, i32), HashSet<i32>>::new();
+    let mut saved_tops = HashMap::<(i32, i32), i32>::new();
+
+    for pos in storage.chunk_positions()?.into_iter().filter(in_range) {
+        saved_levels
+            .entry((pos.x, pos.z))
+            .or_default()
+            .insert(pos.y);
+
+        let (_, indices) = decode_palette(&decompress_chunk(&load(storage, pos)?)?, CHUNK_VOLUME)?;
+
+        for (i, _) in indices.iter().enumerate().filter(|(_, &index)| index != 0) {
+            let (x, y, z) = (
+                i % CHUNK_SIZE,
+                i / CHUNK_SIZE % CHUNK_SIZE,
+                i / (CHUNK_SIZE * CHUNK_SIZE),
+            );
+            let column = (pos.x * size + x as i32, pos.z * size + z as i32);
+            let top = saved_tops.entry(column).or_insert(i32::MIN);
+            *top = (*top).max(pos.y * size + y as i32);
+        }
+    }
+
+    let width = ((radius * 2 + 1) * size) as u32;
+    let origin = -radius * size;
+    let mut heights = Vec::with_capacity((width * width) as usize);
+
+    for z in origin..origin + width as i32 {
+        for x in origin..origin + width as i32 {
+            let levels = saved_levels.get(&(x.div_euclid(size), z.div_euclid(size)));
+
+            // Generated terrain is solid all the way down, so its top is the generated height
+            // unless that chunk was saved, in which case it is under the lowest saved chunk.
+            let mut generated = terrain_height(noise, x, z);
+            while levels.is_some_and(|levels| levels.contains(&generated.div_euclid(size))) {
+                generated = generated.div_euclid(size) * size - 1;
+            }
+
+            let saved = saved_tops.get(&(x, z)).copied().unwrap_or(i32::MIN);
+            heights.push(generated.max(saved));
+        }
+    }
+
+    let min = heights.iter().copied().min().unwrap();
+    let max = heights.iter().copied().max().unwrap();
+    let range = (max - min).max(1) as f32;
+
+    let image = GrayImage::from_fn(width, width, |x, y| {
+        let height = heights[(y * width + x) as usize];
+        image::Luma([((height - min) as f32 / range * 255.0) as u8])
+    });
+    image.save(output)?;
+
+    println!("Wrote {width}x{width} heightmap of heights {min} to {max} to {output}");
+    Ok(())
+}
@@ -31,6 +31,14 @@ impl BlockRegistry {
         self.names[name]
     }
 
+    pub fn get_block_id(&self, name: &str) -> Option<BlockId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(String::as_str)
+    }
+
     pub fn name(&self, id: BlockId) -> &str {
         self.names
             .iter()
@@ -4,13 +4,19 @@ use bevy::prelude::*;
 use ron::ser::PrettyConfig;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    level::{ChunkCompression, ColliderShape, StorageBackend},
+    player::PlayerPhysics,
+};
+
 const CONFIG_PATH: &str = "config.ron";
 
 pub struct ConfigPlugin;
 
 impl Plugin for ConfigPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, load_config)
+        // Loaded while building, so startup systems such as `setup_level` can read it.
+        app.insert_resource(load_config())
             .add_systems(Update, save_config);
     }
 }
@@ -21,17 +27,15 @@ fn write_config_file(config: &Config) {
     fs::write(CONFIG_PATH, text).unwrap();
 }
 
-fn load_config(mut commands: Commands) {
-    let config = if Path::new(CONFIG_PATH).exists() {
+fn load_config() -> Config {
+    if Path::new(CONFIG_PATH).exists() {
         let text = fs::read_to_string(CONFIG_PATH).unwrap();
         ron::from_str(&text).unwrap()
     } else {
         let config = Config::default();
         write_config_file(&config);
         config
-    };
-
-    commands.insert_resource(config);
+    }
 }
 
 fn save_config(config: Res<Config>) {
@@ -41,20 +45,56 @@ fn save_config(config: Res<Config>) {
 }
 
 #[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    /// Horizontal render distance in chunks.
     pub render_distance: i32,
+    /// Chunks loaded above and below the player.
+    pub vertical_render_distance: i32,
+    /// Chunks further than this are meshed in less detail and without colliders.
+    pub lod_distance: i32,
+    /// How many chunks around each dynamic body get colliders, at least one.
+    pub collider_distance: i32,
+    pub collider_shape: ColliderShape,
+    /// Unloaded chunks kept in memory in case they are needed again.
+    pub chunk_cache_size: usize,
+    pub chunk_compression: ChunkCompression,
+    pub storage_backend: StorageBackend,
     pub mouse_sensitivity: f32,
     pub movement_speed: f32,
+    pub player_physics: PlayerPhysics,
     pub movement_controls: MovementControls,
+    pub edit_controls: EditControls,
+    pub edit_history_limit: usize,
+    pub schematic_directory: String,
+    pub daylight_cycle: bool,
+    pub day_length: f32,
+    pub console_controls: ConsoleControls,
+    pub network: NetworkConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             render_distance: 8,
+            vertical_render_distance: 4,
+            lod_distance: 8,
+            collider_distance: 1,
+            collider_shape: ColliderShape::default(),
+            chunk_cache_size: 256,
+            chunk_compression: ChunkCompression::default(),
+            storage_backend: StorageBackend::default(),
             mouse_sensitivity: 0.00012,
             movement_speed: 70.0,
+            player_physics: PlayerPhysics::default(),
             movement_controls: MovementControls::default(),
+            edit_controls: EditControls::default(),
+            edit_history_limit: 100,
+            schematic_directory: "schematics".to_string(),
+            daylight_cycle: false,
+            day_length: 1200.0,
+            console_controls: ConsoleControls::default(),
+            network: NetworkConfig::default(),
         }
     }
 }
@@ -81,3 +121,79 @@ impl Default for MovementControls {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditControls {
+    pub modifier: KeyCode,
+    pub undo: KeyCode,
+    pub redo: KeyCode,
+    pub first_corner: KeyCode,
+    pub second_corner: KeyCode,
+    pub copy: KeyCode,
+    pub cut: KeyCode,
+    pub paste: KeyCode,
+    pub rotate: KeyCode,
+    pub clear: KeyCode,
+    pub fill: KeyCode,
+    pub replace: KeyCode,
+    pub hollow: KeyCode,
+    pub export: KeyCode,
+}
+
+impl Default for EditControls {
+    fn default() -> Self {
+        Self {
+            modifier: KeyCode::ControlLeft,
+            undo: KeyCode::Z,
+            redo: KeyCode::Y,
+            first_corner: KeyCode::Q,
+            second_corner: KeyCode::E,
+            copy: KeyCode::C,
+            cut: KeyCode::X,
+            paste: KeyCode::V,
+            rotate: KeyCode::R,
+            clear: KeyCode::Delete,
+            fill: KeyCode::F,
+            replace: KeyCode::G,
+            hollow: KeyCode::H,
+            export: KeyCode::O,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsoleControls {
+    pub open: KeyCode,
+    pub open_command: KeyCode,
+}
+
+impl Default for ConsoleControls {
+    fn default() -> Self {
+        Self {
+            open: KeyCode::T,
+            open_command: KeyCode::Slash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Joins this server instead of playing the local world when set.
+    pub server_address: Option<String>,
+    /// Where the dedicated server listens.
+    pub bind_address: String,
+    pub player_name: String,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            server_address: None,
+            bind_address: "0.0.0.0:25565".to_string(),
+            player_name: "Player".to_string(),
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+
+use crate::{
+    config::Config,
+    player::{grab, ungrab},
+};
+
+mod commands;
+mod parser;
+
+pub use commands::*;
+pub use parser::*;
+
+const MAX_OUTPUT_LINES: usize = 100;
+const VISIBLE_OUTPUT_LINES: usize = 12;
+const FONT_PATH: &str = "fonts/UbuntuMonoNerdFontCompleteMono.ttf";
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let mut registry = CommandRegistry::default();
+        register_commands(&mut registry);
+
+        app.insert_resource(registry)
+            .init_resource::<Console>()
+            .add_systems(Startup, setup_console)
+            .add_systems(
+                Update,
+                (console_input, run_commands, update_console_text).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    pending: Vec<String>,
+    completion_requested: bool,
+}
+
+impl Console {
+    pub fn print(&mut self, text: impl Into<String>) {
+        for line in text.into().lines() {
+            self.output.push_back(line.to_string());
+        }
+
+        while self.output.len() > MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    /// Queues a line to be run as if it were typed into the console.
+    pub fn submit(&mut self, line: String) {
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.history_index = None;
+        self.pending.push(line);
+    }
+}
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn setup_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(ConsoleRoot)
+        .insert(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(8.0),
+                bottom: Val::Px(8.0),
+                width: Val::Percent(60.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .with_children(|commands| {
+            commands
+                .spawn(ConsoleText)
+                .insert(TextBundle::from_sections([
+                    TextSection::new("", style.clone()),
+                    TextSection::new("", style),
+                ]));
+        });
+}
+
+fn console_input(
+    mut console: ResMut<Console>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+    keyboard: Res<Input<KeyCode>>,
+    config: Res<Config>,
+) {
+    let typed: String = characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|char| !char.is_control())
+        .collect();
+
+    let mut window = primary_window.single_mut();
+
+    if !console.open {
+        let controls = &config.console_controls;
+        let command = keyboard.just_pressed(controls.open_command);
+
+        if window.cursor.grab_mode != CursorGrabMode::None
+            && (command || keyboard.just_pressed(controls.open))
+        {
+            console.open = true;
+            console.input = if command {
+                "/".to_string()
+            } else {
+                String::new()
+            };
+            ungrab(&mut window);
+        }
+
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Escape) {
+        console.open = false;
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+
+        if !line.trim().is_empty() {
+            console.submit(line);
+        }
+
+        console.open = false;
+        grab(&mut window);
+        return;
+    }
+
+    console.input.push_str(&typed);
+
+    if keyboard.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+
+    if keyboard.just_pressed(KeyCode::Up) && !console.history.is_empty() {
+        let index = console
+            .history_index
+            .map_or(console.history.len() - 1, |index| index.saturating_sub(1));
+        console.history_index = Some(index);
+        console.input = console.history[index].clone();
+    }
+
+    if keyboard.just_pressed(KeyCode::Down) {
+        if let Some(index) = console.history_index {
+            if index + 1 < console.history.len() {
+                console.history_index = Some(index + 1);
+                console.input = console.history[index + 1].clone();
+            } else {
+                console.history_index = None;
+                console.input.clear();
+            }
+        }
+    }
+
+    if keyboard.just_pressed(KeyCode::Tab) {
+        console.completion_requested = true;
+    }
+}
+
+/// Completes the word being typed: command names first, then the command's arguments.
+fn complete_input(world: &mut World) {
+    let console = world.resource::<Console>();
+    let Some(line) = console.input.strip_prefix('/').map(str::to_string) else {
+        return;
+    };
+
+    let start = line.rfind(' ').map_or(0, |index| index + 1);
+    let prefix = &line[start..];
+    let registry = world.resource::<CommandRegistry>();
+
+    let candidates = if start == 0 {
+        registry.names().map(str::to_string).collect()
+    } else {
+        let name = line.split(' ').next().unwrap_or_default();
+        registry
+            .command(name)
+            .map(|command| (command.complete)(world))
+            .unwrap_or_default()
+    };
+
+    let Some((completed, matches)) = complete(prefix, candidates.iter().map(String::as_str)) else {
+        return;
+    };
+
+    let mut console = world.resource_mut::<Console>();
+    console.input = format!("/{}{completed}", &line[..start]);
+
+    if matches.len() == 1 {
+        console.input.push(' ');
+    } else if completed == prefix {
+        console.print(matches.join("  "));
+    }
+}
+
+fn run_commands(world: &mut World) {
+    let console = world.resource::<Console>();
+
+    if console.pending.is_empty() && !console.completion_requested {
+        return;
+    }
+
+    let pending = std::mem::take(&mut world.resource_mut::<Console>().pending);
+
+    for line in pending {
+        let Some((name, args)) = parse_command(&line) else {
+            // Plain text is echoed, ready for chat once there are other players to read it.
+            world.resource_mut::<Console>().print(line.clone());
+            continue;
+        };
+
+        world.resource_mut::<Console>().print(format!("> {line}"));
+
+        let Some(run) = world
+            .resource::<CommandRegistry>()
+            .command(name)
+            .map(|command| command.run)
+        else {
+            let message = format!("Unknown command `{name}`, try /help");
+            world.resource_mut::<Console>().print(message);
+            continue;
+        };
+
+        let (Ok(message) | Err(message)) = run(world, &args);
+        world.resource_mut::<Console>().print(message);
+    }
+
+    if std::mem::take(&mut world.resource_mut::<Console>().completion_requested) {
+        complete_input(world);
+    }
+}
+
+fn update_console_text(
+    console: Res<Console>,
+    mut root: Query<&mut Visibility, With<ConsoleRoot>>,
+    mut text: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    *root.single_mut() = if console.open {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    let mut text = text.single_mut();
+    let skip = console.output.len().saturating_sub(VISIBLE_OUTPUT_LINES);
+    let mut output = String::new();
+
+    for line in console.output.iter().skip(skip) {
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    text.sections[0].value = output;
+    text.sections[1].value = format!("{}_", console.input);
+}
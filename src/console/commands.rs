@@ -0,0 +1,366 @@
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use bevy_rapier3d::prelude::Velocity;
+use noise::Seedable;
+
+use crate::{
+    block_registry::{BlockId, SharedBlockRegistry},
+    config::Config,
+    edit::{player_anchor, Region, WorldEdit, MAX_FILL_VOLUME},
+    level::{Level, TaskCounts, TaskStats, MAX_RENDER_DISTANCE},
+    player::{GameMode, Player},
+    position::BlockPos,
+    sky::TimeOfDay,
+};
+
+use super::parser::parse_coordinate;
+
+pub type CommandResult = Result<String, String>;
+
+pub struct ConsoleCommand {
+    pub usage: &'static str,
+    pub run: fn(&mut World, &[&str]) -> CommandResult,
+    /// Words offered when tab-completing the command's arguments.
+    pub complete: fn(&World) -> Vec<String>,
+}
+
+#[derive(Resource, Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, ConsoleCommand>,
+}
+
+impl CommandRegistry {
+    pub fn register(&mut self, name: String, command: ConsoleCommand) {
+        self.commands.insert(name, command);
+    }
+
+    pub fn command(&self, name: &str) -> Option<&ConsoleCommand> {
+        self.commands.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(String::as_str)
+    }
+}
+
+pub fn register_commands(registry: &mut CommandRegistry) {
+    macro_rules! register {
+        ( $( $name:literal => $run:ident, $complete:ident, $usage:literal; )* ) => {
+            $( registry.register(
+                $name.to_string(),
+                ConsoleCommand {
+                    usage: $usage,
+                    run: $run,
+                    complete: $complete,
+                },
+            ); )*
+        };
+    }
+
+    register!(
+        "help" => help, command_names, "/help [command]";
+        "tp" => teleport, no_words, "/tp <x> <y> <z>";
+        "setblock" => set_block, block_names, "/setblock <x> <y> <z> <block>";
+        "fill" => fill, fill_words, "/fill <x1> <y1> <z1> <x2> <y2> <z2> <block> [hollow]";
+        "seed" => seed, no_words, "/seed";
+        "time" => time, time_words, "/time [set <hour|day|noon|night|midnight>]";
+        "gamemode" => game_mode, game_mode_names, "/gamemode <survival|creative|spectator>";
+        "render_distance" => render_distance, no_words, "/render_distance [chunks] [vertical chunks]";
+        "tasks" => tasks, no_words, "/tasks";
+    );
+}
+
+fn usage_error(world: &World, name: &str) -> String {
+    let registry = world.resource::<CommandRegistry>();
+    format!("Usage: {}", registry.command(name).unwrap().usage)
+}
+
+fn no_words(_world: &World) -> Vec<String> {
+    Vec::new()
+}
+
+fn command_names(world: &World) -> Vec<String> {
+    let registry = world.resource::<CommandRegistry>();
+    registry.names().map(str::to_string).collect()
+}
+
+fn block_names(world: &World) -> Vec<String> {
+    let registry = world.resource::<SharedBlockRegistry>().read().unwrap();
+    registry
+        .names()
+        .map(str::to_string)
+        .chain(["air".to_string()])
+        .collect()
+}
+
+fn fill_words(world: &World) -> Vec<String> {
+    let mut words = block_names(world);
+    words.push("hollow".to_string());
+    words
+}
+
+fn time_words(_world: &World) -> Vec<String> {
+    let mut words = vec!["set".to_string()];
+    words.extend(TimeOfDay::NAMED.map(|item| item.0.to_string()));
+    words
+}
+
+fn game_mode_names(_world: &World) -> Vec<String> {
+    GameMode::ALL.map(|mode| mode.name().to_string()).to_vec()
+}
+
+fn player_transform(world: &mut World) -> Result<Transform, String> {
+    world
+        .query_filtered::<&Transform, With<Player>>()
+        .get_single(world)
+        .copied()
+        .map_err(|_| "There is no player".to_string())
+}
+
+fn parse_block_pos(args: &[&str], origin: BlockPos) -> Result<BlockPos, String> {
+    let x = parse_coordinate(args[0], origin.x as f32)?;
+    let y = parse_coordinate(args[1], origin.y as f32)?;
+    let z = parse_coordinate(args[2], origin.z as f32)?;
+    Ok(BlockPos::from(Vec3::new(x, y, z).floor()))
+}
+
+fn parse_block(world: &World, name: &str) -> Result<Option<BlockId>, String> {
+    if name == "air" {
+        return Ok(None);
+    }
+
+    let registry = world.resource::<SharedBlockRegistry>().read().unwrap();
+    registry
+        .get_block_id(name)
+        .map(Some)
+        .ok_or_else(|| format!("Unknown block `{name}`"))
+}
+
+fn help(world: &mut World, args: &[&str]) -> CommandResult {
+    let registry = world.resource::<CommandRegistry>();
+
+    match args {
+        [] => Ok(registry
+            .commands
+            .values()
+            .map(|command| command.usage)
+            .collect::<Vec<_>>()
+            .join("\n")),
+        [name] => registry
+            .command(name.trim_start_matches('/'))
+            .map(|command| command.usage.to_string())
+            .ok_or_else(|| format!("Unknown command `{name}`")),
+        _ => Err(usage_error(world, "help")),
+    }
+}
+
+fn teleport(world: &mut World, args: &[&str]) -> CommandResult {
+    let [x, y, z] = args else {
+        return Err(usage_error(world, "tp"));
+    };
+
+    let origin = player_transform(world)?.translation;
+    let target = Vec3::new(
+        parse_coordinate(x, origin.x)?,
+        parse_coordinate(y, origin.y)?,
+        parse_coordinate(z, origin.z)?,
+    );
+
+    let mut player = world.query_filtered::<(&mut Transform, &mut Velocity), With<Player>>();
+    let (mut transform, mut velocity) = player.single_mut(world);
+    transform.translation = target;
+    *velocity = Velocity::default();
+
+    Ok(format!(
+        "Teleported to {:.1} {:.1} {:.1}",
+        target.x, target.y, target.z
+    ))
+}
+
+fn set_block(world: &mut World, args: &[&str]) -> CommandResult {
+    let [coordinates @ .., block] = args else {
+        return Err(usage_error(world, "setblock"));
+    };
+
+    if coordinates.len() != 3 {
+        return Err(usage_error(world, "setblock"));
+    }
+
+    let origin = player_anchor(&player_transform(world)?);
+    let pos = parse_block_pos(coordinates, origin)?;
+    let block = parse_block(world, block)?;
+
+    if world.resource::<Level>().block(pos).is_none() {
+        return Err("That position is not loaded".to_string());
+    }
+
+    let mut state = SystemState::<WorldEdit>::new(world);
+    state.get_mut(world).set_block(pos, block);
+    state.apply(world);
+
+    Ok(format!(
+        "Changed the block at {} {} {}",
+        pos.x, pos.y, pos.z
+    ))
+}
+
+fn fill(world: &mut World, args: &[&str]) -> CommandResult {
+    let (coordinates, block, hollow) = match args {
+        [coordinates @ .., block, "hollow"] => (coordinates, block, true),
+        [coordinates @ .., block] => (coordinates, block, false),
+        _ => return Err(usage_error(world, "fill")),
+    };
+
+    if coordinates.len() != 6 {
+        return Err(usage_error(world, "fill"));
+    }
+
+    let origin = player_anchor(&player_transform(world)?);
+    let region = Region::new(
+        parse_block_pos(&coordinates[..3], origin)?,
+        parse_block_pos(&coordinates[3..], origin)?,
+    );
+    let block = parse_block(world, block)?;
+
+    let volume = region.volume();
+
+    if volume > MAX_FILL_VOLUME {
+        return Err(format!(
+            "Too many blocks in the region ({volume} > {MAX_FILL_VOLUME})"
+        ));
+    }
+
+    let mut state = SystemState::<WorldEdit>::new(world);
+    let mut edit = state.get_mut(world);
+
+    if hollow {
+        edit.hollow(region, block);
+    } else {
+        edit.fill(region, block);
+    }
+
+    state.apply(world);
+
+    Ok(format!("Filled {volume} blocks"))
+}
+
+fn seed(world: &mut World, args: &[&str]) -> CommandResult {
+    if !args.is_empty() {
+        return Err(usage_error(world, "seed"));
+    }
+
+    Ok(format!(
+        "Seed: {}",
+        world.resource::<Level>().noise().seed()
+    ))
+}
+
+fn time(world: &mut World, args: &[&str]) -> CommandResult {
+    let format_time = |time: f32| {
+        let minutes = (time * 24.0 * 60.0).round() as u32 % (24 * 60);
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    };
+
+    match args {
+        [] => Ok(format!(
+            "The time is {}",
+            format_time(world.resource::<TimeOfDay>().0)
+        )),
+        ["set", value] => {
+            let time = TimeOfDay::NAMED
+                .iter()
+                .find(|item| item.0 == *value)
+                .map(|item| item.1)
+                .or_else(|| {
+                    value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|hour| (0.0..24.0).contains(hour))
+                        .map(|hour| hour / 24.0)
+                })
+                .ok_or_else(|| format!("Expected an hour from 0 to 24 but found `{value}`"))?;
+
+            world.resource_mut::<TimeOfDay>().0 = time;
+            Ok(format!("Set the time to {}", format_time(time)))
+        }
+        _ => Err(usage_error(world, "time")),
+    }
+}
+
+fn game_mode(world: &mut World, args: &[&str]) -> CommandResult {
+    let [name] = args else {
+        return Err(usage_error(world, "gamemode"));
+    };
+
+    let mode = GameMode::ALL
+        .into_iter()
+        .find(|mode| mode.name() == *name)
+        .ok_or_else(|| format!("Unknown game mode `{name}`"))?;
+
+    let mut player = world.query_filtered::<&mut GameMode, With<Player>>();
+    *player
+        .get_single_mut(world)
+        .map_err(|_| "There is no player".to_string())? = mode;
+
+    Ok(format!("Set the game mode to {}", mode.name()))
+}
+
+fn render_distance(world: &mut World, args: &[&str]) -> CommandResult {
+    let parse = |value: &str| {
+        value
+            .parse::<i32>()
+            .ok()
+            .filter(|distance| (1..=MAX_RENDER_DISTANCE).contains(distance))
+            .ok_or_else(|| {
+                format!("Expected 1 to {MAX_RENDER_DISTANCE} chunks but found `{value}`")
+            })
+    };
+
+    match args {
+        [] => {
+            let config = world.resource::<Config>();
+            Ok(format!(
+                "The render distance is {} chunks, and {} chunks vertically",
+                config.render_distance, config.vertical_render_distance
+            ))
+        }
+        [value] => {
+            let distance = parse(value)?;
+            world.resource_mut::<Config>().render_distance = distance;
+            Ok(format!("Set the render distance to {distance} chunks"))
+        }
+        [value, vertical] => {
+            let distance = parse(value)?;
+            let vertical = parse(vertical)?;
+            let mut config = world.resource_mut::<Config>();
+            config.render_distance = distance;
+            config.vertical_render_distance = vertical;
+            Ok(format!(
+                "Set the render distance to {distance} chunks, and {vertical} chunks vertically"
+            ))
+        }
+        _ => Err(usage_error(world, "render_distance")),
+    }
+}
+
+fn tasks(world: &mut World, args: &[&str]) -> CommandResult {
+    if !args.is_empty() {
+        return Err(usage_error(world, "tasks"));
+    }
+
+    let format_counts = |name, counts: TaskCounts| {
+        format!(
+            "{name}: {} queued, {} running, {} done, {} cancelled",
+            counts.queued, counts.in_flight, counts.completed, counts.cancelled
+        )
+    };
+
+    let stats = world.resource::<TaskStats>();
+    Ok(format!(
+        "{}\n{}\n{}",
+        format_counts("Loading", stats.generate),
+        format_counts("Meshing", stats.mesh),
+        format_counts("Colliders", stats.collider)
+    ))
+}
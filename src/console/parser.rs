@@ -0,0 +1,87 @@
+/// Splits a command line such as `/tp 0 ~10 0` into its name and arguments.
+pub fn parse_command(line: &str) -> Option<(&str, Vec<&str>)> {
+    let mut words = line.strip_prefix('/')?.split_whitespace();
+    let name = words.next()?;
+    Some((name, words.collect()))
+}
+
+/// Parses an absolute coordinate, or one relative to `origin` when prefixed with `~`.
+pub fn parse_coordinate(arg: &str, origin: f32) -> Result<f32, String> {
+    let (relative, value) = match arg.strip_prefix('~') {
+        Some("") => return Ok(origin),
+        Some(value) => (true, value),
+        None => (false, arg),
+    };
+
+    let value = value
+        .parse::<f32>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| format!("Expected a coordinate but found `{arg}`"))?;
+
+    Ok(if relative { origin + value } else { value })
+}
+
+/// Extends `prefix` as far as every matching candidate agrees, returning the completed word and
+/// the candidates that still match it.
+pub fn complete<'a>(
+    prefix: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<(String, Vec<&'a str>)> {
+    let mut matches: Vec<&str> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(prefix))
+        .collect();
+
+    matches.sort_unstable();
+    matches.dedup();
+
+    let mut completed = matches.first()?.to_string();
+
+    for candidate in &matches[1..] {
+        while !candidate.starts_with(&completed) {
+            completed.pop();
+        }
+    }
+
+    Some((completed, matches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command("/tp 1 ~ 2"),
+            Some(("tp", vec!["1", "~", "2"]))
+        );
+        assert_eq!(parse_command("/seed"), Some(("seed", vec![])));
+        assert_eq!(parse_command("hello"), None);
+        assert_eq!(parse_command("/"), None);
+    }
+
+    #[test]
+    fn test_parse_coordinate() {
+        assert_eq!(parse_coordinate("~", 4.0), Ok(4.0));
+        assert_eq!(parse_coordinate("~-1.5", 4.0), Ok(2.5));
+        assert_eq!(parse_coordinate("7", 4.0), Ok(7.0));
+        assert!(parse_coordinate("x", 4.0).is_err());
+    }
+
+    #[test]
+    fn test_complete() {
+        let names = ["dirt", "diamond_ore", "stone"];
+
+        assert_eq!(
+            complete("d", names),
+            Some(("di".to_string(), vec!["diamond_ore", "dirt"]))
+        );
+        assert_eq!(
+            complete("st", names),
+            Some(("stone".to_string(), vec!["stone"]))
+        );
+        assert_eq!(complete("x", names), None);
+    }
+}
@@ -0,0 +1,259 @@
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+    window::{CursorGrabMode, PrimaryWindow},
+};
+use itertools::Itertools;
+
+use crate::{
+    block_registry::BlockId,
+    config::Config,
+    level::{ChunkEntities, Dirty, Level, CHUNK_SIZE},
+    position::{BlockPos, ChunkPos},
+};
+
+mod history;
+mod nbt;
+mod region;
+mod schematic;
+mod selection;
+
+pub use history::*;
+pub use region::*;
+pub use schematic::*;
+pub use selection::*;
+
+pub struct EditPlugin;
+
+impl Plugin for EditPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditHistory>()
+            .add_event::<BlocksEdited>()
+            .init_resource::<Selection>()
+            .init_resource::<Clipboard>()
+            .add_systems(
+                Update,
+                (
+                    undo_redo,
+                    (select_corners, selection_controls).chain(),
+                    clipboard_files,
+                    draw_selection,
+                ),
+            );
+    }
+}
+
+/// Sent for every local edit that changed at least one block, so it can be shared with other
+/// players.
+#[derive(Event, Debug, Clone)]
+pub struct BlocksEdited(pub Transaction);
+
+/// The single path through which the world is edited, so every change is recorded in the
+/// [`EditHistory`] and the affected chunks are re-meshed and saved.
+#[derive(SystemParam)]
+pub struct WorldEdit<'w, 's> {
+    commands: Commands<'w, 's>,
+    level: ResMut<'w, Level>,
+    history: ResMut<'w, EditHistory>,
+    edited: EventWriter<'w, BlocksEdited>,
+    config: Res<'w, Config>,
+    entities: Res<'w, ChunkEntities>,
+}
+
+impl<'w, 's> WorldEdit<'w, 's> {
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn set_block(&mut self, pos: BlockPos, block: Option<BlockId>) {
+        self.set_blocks([(pos, block)]);
+    }
+
+    /// Applies the changes as one undoable step, skipping positions in unloaded chunks.
+    pub fn set_blocks(&mut self, blocks: impl IntoIterator<Item = (BlockPos, Option<BlockId>)>) {
+        let mut transaction = Transaction::new();
+
+        for (pos, block) in blocks {
+            transaction.push(BlockChange {
+                pos,
+                before: None,
+                after: block,
+            });
+        }
+
+        let applied = self.apply(&transaction);
+        self.notify(&applied);
+        self.history.record(applied, self.config.edit_history_limit);
+    }
+
+    /// Applies changes made elsewhere, such as by a server, without recording them or sending
+    /// [`BlocksEdited`].
+    pub fn sync_blocks(&mut self, blocks: impl IntoIterator<Item = (BlockPos, Option<BlockId>)>) {
+        let mut transaction = Transaction::new();
+
+        for (pos, block) in blocks {
+            transaction.push(BlockChange {
+                pos,
+                before: None,
+                after: block,
+            });
+        }
+
+        self.apply(&transaction);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(transaction) = self.history.undo() else {
+            return false;
+        };
+        let applied = self.apply(&transaction);
+        self.notify(&applied);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(transaction) = self.history.redo() else {
+            return false;
+        };
+        let applied = self.apply(&transaction);
+        self.notify(&applied);
+        true
+    }
+
+    pub fn fill(&mut self, region: Region, block: Option<BlockId>) {
+        self.set_blocks(region.iter().map(|pos| (pos, block)));
+    }
+
+    pub fn replace(&mut self, region: Region, from: Option<BlockId>, to: Option<BlockId>) {
+        let blocks = region
+            .iter()
+            .filter(|&pos| self.level.block(pos) == Some(from))
+            .map(|pos| (pos, to))
+            .collect_vec();
+        self.set_blocks(blocks);
+    }
+
+    /// Fills the faces of the region with `block` and clears everything inside them.
+    pub fn hollow(&mut self, region: Region, block: Option<BlockId>) {
+        self.set_blocks(
+            region
+                .iter()
+                .map(|pos| (pos, if region.is_shell(pos) { block } else { None })),
+        );
+    }
+
+    /// Copies the region into a schematic anchored at `anchor`. Unloaded blocks are copied as air.
+    pub fn copy(&self, region: Region, anchor: BlockPos) -> Schematic {
+        let mut schematic = Schematic::new(region.size(), region.min - anchor);
+
+        for pos in region.iter() {
+            *schematic.block_mut(pos - region.min) = self.level.block(pos).flatten();
+        }
+
+        schematic
+    }
+
+    pub fn paste(&mut self, schematic: &Schematic, anchor: BlockPos) {
+        let min = anchor + schematic.offset();
+        self.set_blocks(schematic.iter().map(|(pos, block)| (min + pos, block)));
+    }
+
+    fn notify(&mut self, applied: &Transaction) {
+        if !applied.is_empty() {
+            self.edited.send(BlocksEdited(applied.clone()));
+        }
+    }
+
+    /// Writes the `after` side of each change, returning the changes that actually took place
+    /// with their real `before` values. Changes are grouped by chunk so each chunk is looked up
+    /// and marked dirty once, however many of its blocks change.
+    fn apply(&mut self, transaction: &Transaction) -> Transaction {
+        let mut applied = Transaction::new();
+        let mut grouped: HashMap<ChunkPos, Vec<(BlockChange, (usize, usize, usize))>> =
+            HashMap::new();
+        let mut dirty = HashSet::new();
+
+        for &change in transaction.changes() {
+            let (chunk_pos, local) = change.pos.chunk_pos();
+            grouped.entry(chunk_pos).or_default().push((change, local));
+        }
+
+        for (chunk_pos, changes) in grouped {
+            let Some(chunk) = self.level.chunk_mut(chunk_pos) else {
+                continue;
+            };
+
+            for (change, (x, y, z)) in changes {
+                let before = std::mem::replace(chunk.block_mut(x, y, z), change.after);
+
+                if before == change.after {
+                    continue;
+                }
+
+                chunk.set_modified(true);
+
+                applied.push(BlockChange {
+                    pos: change.pos,
+                    before,
+                    after: change.after,
+                });
+
+                dirty.insert(chunk_pos);
+                dirty.extend(touched_neighbours(chunk_pos, x, y, z));
+            }
+        }
+
+        for entity in dirty.into_iter().filter_map(|pos| self.entities.get(pos)) {
+            self.commands.entity(entity).insert(Dirty);
+        }
+
+        applied
+    }
+}
+
+/// Neighbouring chunks whose faces border the block at the given chunk-relative position.
+fn touched_neighbours(
+    chunk_pos: ChunkPos,
+    x: usize,
+    y: usize,
+    z: usize,
+) -> impl Iterator<Item = ChunkPos> {
+    let last = CHUNK_SIZE - 1;
+
+    [
+        (x == 0, chunk_pos - ChunkPos::X),
+        (x == last, chunk_pos + ChunkPos::X),
+        (y == 0, chunk_pos - ChunkPos::Y),
+        (y == last, chunk_pos + ChunkPos::Y),
+        (z == 0, chunk_pos - ChunkPos::Z),
+        (z == last, chunk_pos + ChunkPos::Z),
+    ]
+    .into_iter()
+    .filter(|item| item.0)
+    .map(|item| item.1)
+}
+
+fn undo_redo(
+    mut edit: WorldEdit,
+    keyboard: Res<Input<KeyCode>>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let window = primary_window.single();
+
+    if window.cursor.grab_mode == CursorGrabMode::None {
+        return;
+    }
+
+    let controls = edit.config.edit_controls.clone();
+
+    if !keyboard.pressed(controls.modifier) {
+        return;
+    }
+
+    if keyboard.just_pressed(controls.undo) {
+        edit.undo();
+    } else if keyboard.just_pressed(controls.redo) {
+        edit.redo();
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::Resource;
+
+use crate::{block_registry::BlockId, position::BlockPos};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockChange {
+    pub pos: BlockPos,
+    pub before: Option<BlockId>,
+    pub after: Option<BlockId>,
+}
+
+impl BlockChange {
+    pub fn inverse(self) -> Self {
+        Self {
+            pos: self.pos,
+            before: self.after,
+            after: self.before,
+        }
+    }
+}
+
+/// A group of block changes that is undone and redone as a single step.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transaction {
+    changes: Vec<BlockChange>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, change: BlockChange) {
+        self.changes.push(change);
+    }
+
+    pub fn changes(&self) -> &[BlockChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The transaction which reverts this one, applying its changes in reverse order.
+    pub fn inverse(&self) -> Self {
+        Self {
+            changes: self
+                .changes
+                .iter()
+                .rev()
+                .map(|change| change.inverse())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct EditHistory {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+}
+
+impl EditHistory {
+    /// Records a newly applied transaction, discarding the oldest entries beyond `limit`.
+    pub fn record(&mut self, transaction: Transaction, limit: usize) {
+        if transaction.is_empty() {
+            return;
+        }
+
+        self.redo.clear();
+        self.undo.push_back(transaction);
+
+        while self.undo.len() > limit {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Pops the most recent transaction, returning the changes that revert it.
+    pub fn undo(&mut self) -> Option<Transaction> {
+        let transaction = self.undo.pop_back()?;
+        let inverse = transaction.inverse();
+        self.redo.push(transaction);
+        Some(inverse)
+    }
+
+    /// Pops the most recently undone transaction, returning the changes that reapply it.
+    pub fn redo(&mut self) -> Option<Transaction> {
+        let transaction = self.redo.pop()?;
+        self.undo.push_back(transaction.clone());
+        Some(transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_registry;
+
+    use super::*;
+
+    fn dirt() -> BlockId {
+        test_registry(&["dirt"]).block_id("dirt")
+    }
+
+    fn change(x: i32, before: bool, after: bool) -> BlockChange {
+        BlockChange {
+            pos: BlockPos::new(x, 0, 0),
+            before: before.then(dirt),
+            after: after.then(dirt),
+        }
+    }
+
+    fn transaction(changes: impl IntoIterator<Item = BlockChange>) -> Transaction {
+        let mut transaction = Transaction::new();
+        changes.into_iter().for_each(|item| transaction.push(item));
+        transaction
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = EditHistory::default();
+        let first = transaction([change(0, true, false), change(1, true, false)]);
+        let second = transaction([change(2, false, true)]);
+
+        history.record(first.clone(), 10);
+        history.record(second.clone(), 10);
+
+        assert_eq!(history.undo(), Some(second.inverse()));
+        assert_eq!(
+            history.undo(),
+            Some(transaction([
+                change(1, false, true),
+                change(0, false, true)
+            ]))
+        );
+        assert_eq!(history.undo(), None);
+
+        assert_eq!(history.redo(), Some(first));
+        assert_eq!(history.redo(), Some(second));
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn test_record_clears_redo() {
+        let mut history = EditHistory::default();
+        history.record(transaction([change(0, true, false)]), 10);
+        history.undo();
+        history.record(transaction([change(1, true, false)]), 10);
+
+        assert_eq!(history.redo(), None);
+        assert!(history.undo().is_some());
+        assert_eq!(history.undo(), None);
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let mut history = EditHistory::default();
+
+        for x in 0..5 {
+            history.record(transaction([change(x, true, false)]), 3);
+        }
+
+        for x in (2..5).rev() {
+            assert_eq!(history.undo(), Some(transaction([change(x, false, true)])));
+        }
+        assert_eq!(history.undo(), None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::HashMap, io};
+
+/// A decoded NBT tag, as used by the Sponge schematic format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match *self {
+            Tag::Byte(value) => Some(value as i32),
+            Tag::Short(value) => Some(value as i32),
+            Tag::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&HashMap<String, Tag>> {
+        match self {
+            Tag::Compound(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte_array(&self) -> Option<&[u8]> {
+        match self {
+            Tag::ByteArray(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// Reads an uncompressed NBT document, returning the root tag.
+pub fn read_nbt(bytes: &[u8]) -> io::Result<Tag> {
+    let mut reader = Reader { bytes, i: 0 };
+    let id = reader.u8()?;
+
+    if id != 10 {
+        return Err(invalid("root tag is not a compound"));
+    }
+
+    reader.string()?;
+    reader.payload(id, 0)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    i: usize,
+}
+
+macro_rules! read_be {
+    ( $( $name:ident => $ty:ty; )* ) => {
+        $( fn $name(&mut self) -> io::Result<$ty> {
+            let bytes = self.take(std::mem::size_of::<$ty>())?;
+            Ok(<$ty>::from_be_bytes(bytes.try_into().unwrap()))
+        } )*
+    };
+}
+
+impl<'a> Reader<'a> {
+    read_be!(
+        u8 => u8;
+        i8 => i8;
+        u16 => u16;
+        i16 => i16;
+        i32 => i32;
+        i64 => i64;
+        f32 => f32;
+        f64 => f64;
+    );
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.i..self.i + len)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated NBT data"))?;
+        self.i += len;
+        Ok(slice)
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.i32()?).map_err(|_| invalid("negative NBT length"))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn payload(&mut self, id: u8, depth: usize) -> io::Result<Tag> {
+        if depth > 512 {
+            return Err(invalid("NBT nested too deeply"));
+        }
+
+        Ok(match id {
+            1 => Tag::Byte(self.i8()?),
+            2 => Tag::Short(self.i16()?),
+            3 => Tag::Int(self.i32()?),
+            4 => Tag::Long(self.i64()?),
+            5 => Tag::Float(self.f32()?),
+            6 => Tag::Double(self.f64()?),
+            7 => {
+                let len = self.len()?;
+                Tag::ByteArray(self.take(len)?.to_vec())
+            }
+            8 => Tag::String(self.string()?),
+            9 => {
+                let item_id = self.u8()?;
+                let len = self.len()?;
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.payload(item_id, depth + 1)?);
+                }
+                Tag::List(items)
+            }
+            10 => {
+                let mut entries = HashMap::new();
+                loop {
+                    let item_id = self.u8()?;
+                    if item_id == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    entries.insert(name, self.payload(item_id, depth + 1)?);
+                }
+                Tag::Compound(entries)
+            }
+            11 => {
+                let len = self.len()?;
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.i32()?);
+                }
+                Tag::IntArray(values)
+            }
+            12 => {
+                let len = self.len()?;
+                let mut values = Vec::new();
+                for _ in 0..len {
+                    values.push(self.i64()?);
+                }
+                Tag::LongArray(values)
+            }
+            _ => return Err(invalid("unknown NBT tag")),
+        })
+    }
+}
@@ -0,0 +1,80 @@
+use bevy::prelude::Vec3;
+use itertools::iproduct;
+
+use crate::position::BlockPos;
+
+/// The largest region a single edit, from `/fill` or the selection controls, may cover. Bigger
+/// edits would stall the game and leave an undo entry just as large.
+pub const MAX_FILL_VOLUME: i64 = 1 << 18;
+
+/// An inclusive, axis-aligned cuboid of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub min: BlockPos,
+    pub max: BlockPos,
+}
+
+impl Region {
+    pub fn new(a: BlockPos, b: BlockPos) -> Self {
+        Self {
+            min: BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn size(&self) -> BlockPos {
+        self.max - self.min + BlockPos::new(1, 1, 1)
+    }
+
+    pub fn volume(&self) -> i64 {
+        let size = self.size();
+        size.x as i64 * size.y as i64 * size.z as i64
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (Vec3::from(self.min) + Vec3::from(self.max) + Vec3::ONE) / 2.0
+    }
+
+    pub fn contains(&self, pos: BlockPos) -> bool {
+        (self.min.x..=self.max.x).contains(&pos.x)
+            && (self.min.y..=self.max.y).contains(&pos.y)
+            && (self.min.z..=self.max.z).contains(&pos.z)
+    }
+
+    /// Whether the position lies on one of the six faces of the region.
+    pub fn is_shell(&self, pos: BlockPos) -> bool {
+        self.contains(pos)
+            && (pos.x == self.min.x
+                || pos.x == self.max.x
+                || pos.y == self.min.y
+                || pos.y == self.max.y
+                || pos.z == self.min.z
+                || pos.z == self.max.z)
+    }
+
+    /// Iterates every position in the region, in the same x-fastest order as chunk storage.
+    pub fn iter(&self) -> impl Iterator<Item = BlockPos> {
+        let Region { min, max } = *self;
+        iproduct!(min.z..=max.z, min.y..=max.y, min.x..=max.x)
+            .map(|(z, y, x)| BlockPos::new(x, y, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region() {
+        let region = Region::new(BlockPos::new(2, -1, 0), BlockPos::new(0, 1, 0));
+
+        assert_eq!(region.min, BlockPos::new(0, -1, 0));
+        assert_eq!(region.max, BlockPos::new(2, 1, 0));
+        assert_eq!(region.size(), BlockPos::new(3, 3, 1));
+        assert_eq!(region.volume(), 9);
+        assert_eq!(region.iter().count(), 9);
+        assert_eq!(region.iter().next(), Some(region.min));
+        assert!(region.is_shell(BlockPos::new(1, -1, 0)));
+        assert!(!region.contains(BlockPos::new(3, 0, 0)));
+    }
+}
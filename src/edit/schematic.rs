@@ -0,0 +1,408 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use bevy::{prelude::warn, utils::HashSet};
+use flate2::read::GzDecoder;
+use itertools::Itertools;
+
+use crate::{
+    block_registry::{BlockId, BlockRegistry},
+    level::{decode_palette, encode_palette},
+    position::BlockPos,
+};
+
+use super::nbt::{read_nbt, Tag};
+
+const MAGIC: &[u8; 4] = b"VXSC";
+const VERSION: u8 = 1;
+
+/// The file extension of the native schematic format.
+pub const SCHEMATIC_EXTENSION: &str = "vschem";
+
+/// A copied cuboid of blocks, stored relative to the anchor it was copied from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schematic {
+    size: BlockPos,
+    offset: BlockPos,
+    blocks: Vec<Option<BlockId>>,
+}
+
+impl Schematic {
+    /// Creates an empty schematic whose minimum corner lies at `offset` from the anchor.
+    pub fn new(size: BlockPos, offset: BlockPos) -> Self {
+        let len = size.x as usize * size.y as usize * size.z as usize;
+        Self {
+            size,
+            offset,
+            blocks: vec![None; len],
+        }
+    }
+
+    /// The number of blocks in the schematic.
+    pub fn volume(&self) -> i64 {
+        self.blocks.len() as i64
+    }
+
+    pub fn offset(&self) -> BlockPos {
+        self.offset
+    }
+
+    pub fn block_mut(&mut self, pos: BlockPos) -> &mut Option<BlockId> {
+        let index = self.index(pos);
+        &mut self.blocks[index]
+    }
+
+    /// Iterates every local position and its block, in storage order.
+    pub fn iter(&self) -> impl Iterator<Item = (BlockPos, Option<BlockId>)> + '_ {
+        let size = self.size;
+        self.blocks.iter().enumerate().map(move |(index, &block)| {
+            let index = index as i32;
+            let pos = BlockPos::new(
+                index % size.x,
+                index / size.x % size.y,
+                index / (size.x * size.y),
+            );
+            (pos, block)
+        })
+    }
+
+    /// Rotates the schematic a quarter turn around the vertical axis through its anchor.
+    pub fn rotate(&self) -> Self {
+        let size = BlockPos::new(self.size.z, self.size.y, self.size.x);
+        let offset = BlockPos::new(-self.offset.z - self.size.z, self.offset.y, self.offset.x);
+        let mut rotated = Self::new(size, offset);
+
+        for (pos, block) in self.iter() {
+            *rotated.block_mut(BlockPos::new(self.size.z - 1 - pos.z, pos.y, pos.x)) = block;
+        }
+
+        rotated
+    }
+
+    /// Reads a schematic, choosing the Sponge importer for `.schem` files and the native
+    /// format otherwise.
+    pub fn load(path: &Path, registry: &BlockRegistry) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "schem")
+        {
+            Self::import_sponge(&bytes, registry)
+        } else {
+            Self::deserialize(&bytes, registry)
+        }
+    }
+
+    pub fn save(&self, path: &Path, registry: &BlockRegistry) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.serialize(registry))
+    }
+
+    /// Encodes the schematic as a header holding its size and offset, followed by the same
+    /// palette and run-length encoding that chunks are stored with.
+    pub fn serialize(&self, registry: &BlockRegistry) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.push(VERSION);
+
+        for value in [self.size, self.offset]
+            .into_iter()
+            .flat_map(|pos| [pos.x, pos.y, pos.z])
+        {
+            bytes.extend(value.to_be_bytes());
+        }
+
+        bytes.extend(encode_palette(
+            self.blocks
+                .iter()
+                .map(|block| block.map(|id| registry.name(id))),
+        ));
+        bytes
+    }
+
+    /// Decodes a native schematic. Blocks missing from the registry are replaced with air.
+    pub fn deserialize(bytes: &[u8], registry: &BlockRegistry) -> io::Result<Self> {
+        if bytes.len() < 29 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a schematic file"));
+        }
+
+        if bytes[4] != VERSION {
+            return Err(invalid("unsupported schematic version"));
+        }
+
+        let values = bytes[5..29]
+            .chunks_exact(4)
+            .map(|value| i32::from_be_bytes(value.try_into().unwrap()))
+            .collect_vec();
+        let size = BlockPos::new(values[0], values[1], values[2]);
+        let offset = BlockPos::new(values[3], values[4], values[5]);
+        let mut schematic = Self::checked_new(size, offset)?;
+
+        let (names, indices) = decode_palette(&bytes[29..], schematic.blocks.len())?;
+        let ids = BlockResolver::new(registry).resolve_all(&names);
+
+        for (block, index) in schematic.blocks.iter_mut().zip(indices) {
+            *block = index.checked_sub(1).and_then(|index| ids[index as usize]);
+        }
+
+        Ok(schematic)
+    }
+
+    /// Imports a Sponge schematic (versions 1 to 3), as written by WorldEdit and most other
+    /// editors. Namespaces and block states are dropped, and blocks missing from the registry
+    /// are replaced with air.
+    pub fn import_sponge(bytes: &[u8], registry: &BlockRegistry) -> io::Result<Self> {
+        let mut data = Vec::new();
+
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            GzDecoder::new(bytes).read_to_end(&mut data)?;
+        } else {
+            data.extend(bytes);
+        }
+
+        let root = read_nbt(&data)?;
+        let root = root.get("Schematic").unwrap_or(&root);
+        let blocks = root.get("Blocks").unwrap_or(root);
+
+        let dimension = |name| {
+            root.get(name)
+                .and_then(Tag::as_int)
+                .map(|value| value as u16 as i32)
+                .ok_or_else(|| invalid("schematic is missing its dimensions"))
+        };
+        let (width, height, length) = (
+            dimension("Width")?,
+            dimension("Height")?,
+            dimension("Length")?,
+        );
+
+        let offset = ["WEOffsetX", "WEOffsetY", "WEOffsetZ"].map(|name| {
+            root.get("Metadata")
+                .and_then(|metadata| metadata.get(name))
+                .and_then(Tag::as_int)
+                .unwrap_or_default()
+        });
+
+        let mut schematic = Self::checked_new(
+            BlockPos::new(width, height, length),
+            BlockPos::new(offset[0], offset[1], offset[2]),
+        )?;
+
+        let palette = blocks
+            .get("Palette")
+            .and_then(Tag::as_compound)
+            .ok_or_else(|| invalid("schematic is missing its palette"))?;
+        let mut names = vec![String::new(); palette.len()];
+
+        for (name, index) in palette {
+            let index = index
+                .as_int()
+                .and_then(|index| usize::try_from(index).ok())
+                .filter(|&index| index < names.len())
+                .ok_or_else(|| invalid("invalid palette index"))?;
+            names[index] = sponge_block_name(name).to_string();
+        }
+
+        let ids = BlockResolver::new(registry).resolve_all(&names);
+
+        let data = blocks
+            .get("BlockData")
+            .or_else(|| blocks.get("Data"))
+            .and_then(Tag::as_byte_array)
+            .ok_or_else(|| invalid("schematic is missing its block data"))?;
+        let mut data = data.iter();
+
+        // Sponge stores blocks y-major then z then x, as varint palette indices.
+        for (y, z, x) in itertools::iproduct!(0..height, 0..length, 0..width) {
+            let index = read_varint(&mut data)?;
+            let id = ids
+                .get(index)
+                .ok_or_else(|| invalid("invalid palette index"))?;
+            *schematic.block_mut(BlockPos::new(x, y, z)) = *id;
+        }
+
+        Ok(schematic)
+    }
+
+    fn checked_new(size: BlockPos, offset: BlockPos) -> io::Result<Self> {
+        if size.x <= 0 || size.y <= 0 || size.z <= 0 {
+            return Err(invalid("schematic has no blocks"));
+        }
+
+        if size.x as i64 * size.y as i64 * size.z as i64 > MAX_VOLUME {
+            return Err(invalid("schematic is too large"));
+        }
+
+        Ok(Self::new(size, offset))
+    }
+
+    fn index(&self, pos: BlockPos) -> usize {
+        (pos.x + pos.y * self.size.x + pos.z * self.size.x * self.size.y) as usize
+    }
+}
+
+/// Refuses to allocate schematics larger than this many blocks.
+const MAX_VOLUME: i64 = 256 * 256 * 256;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Strips the namespace and block states, turning `minecraft:oak_log[axis=y]` into `oak_log`.
+fn sponge_block_name(name: &str) -> &str {
+    let name = name.split('[').next().unwrap_or(name);
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn read_varint<'a>(bytes: &mut impl Iterator<Item = &'a u8>) -> io::Result<usize> {
+    let mut value = 0;
+
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes
+            .next()
+            .ok_or_else(|| invalid("truncated schematic block data"))?;
+        value |= ((byte & 0x7f) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid("varint is too long"))
+}
+
+/// Maps palette names to registered blocks, warning once for each unknown name.
+struct BlockResolver<'a> {
+    registry: &'a BlockRegistry,
+    unknown: HashSet<String>,
+}
+
+impl<'a> BlockResolver<'a> {
+    fn new(registry: &'a BlockRegistry) -> Self {
+        Self {
+            registry,
+            unknown: HashSet::new(),
+        }
+    }
+
+    fn resolve_all(mut self, names: &[String]) -> Vec<Option<BlockId>> {
+        names.iter().map(|name| self.resolve(name)).collect_vec()
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<BlockId> {
+        if matches!(name, "air" | "cave_air" | "void_air") {
+            return None;
+        }
+
+        let id = self.registry.get_block_id(name);
+
+        if id.is_none() && self.unknown.insert(name.to_string()) {
+            warn!("unknown block `{name}` in schematic was replaced with air");
+        }
+
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    use crate::{block_registry::BlockRegistry, test_utils::test_registry};
+
+    use super::*;
+
+    fn registry() -> BlockRegistry {
+        test_registry(&["dirt"])
+    }
+
+    #[test]
+    fn test_rotate() {
+        let registry = registry();
+        let dirt = registry.block_id("dirt");
+
+        let mut schematic = Schematic::new(BlockPos::new(3, 1, 2), BlockPos::new(1, 0, 0));
+        *schematic.block_mut(BlockPos::new(2, 0, 0)) = Some(dirt);
+
+        let rotated = schematic.rotate();
+        assert_eq!(rotated.offset(), BlockPos::new(-2, 0, 1));
+        assert_eq!(rotated.iter().count(), 6);
+        assert_eq!(
+            rotated.iter().find(|item| item.1.is_some()),
+            Some((BlockPos::new(1, 0, 2), Some(dirt)))
+        );
+
+        // The block sat at (3, 0, 0) from the anchor, and a quarter turn puts it at (-1, 0, 3).
+        assert_eq!(
+            rotated.offset() + BlockPos::new(1, 0, 2),
+            BlockPos::new(-1, 0, 3)
+        );
+
+        assert_eq!(rotated.rotate().rotate().rotate(), schematic);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let registry = registry();
+        let mut schematic = Schematic::new(BlockPos::new(4, 3, 2), BlockPos::new(-1, 0, 2));
+        *schematic.block_mut(BlockPos::new(3, 2, 1)) = Some(registry.block_id("dirt"));
+
+        let bytes = schematic.serialize(&registry);
+        assert_eq!(
+            Schematic::deserialize(&bytes, &registry).unwrap(),
+            schematic
+        );
+        assert!(Schematic::deserialize(&bytes[..20], &registry).is_err());
+    }
+
+    #[test]
+    fn test_import_sponge() {
+        fn named(bytes: &mut Vec<u8>, id: u8, name: &str) {
+            bytes.push(id);
+            bytes.extend((name.len() as u16).to_be_bytes());
+            bytes.extend(name.as_bytes());
+        }
+
+        let mut nbt = Vec::new();
+        named(&mut nbt, 10, "Schematic");
+        named(&mut nbt, 3, "Version");
+        nbt.extend(2i32.to_be_bytes());
+        for name in ["Width", "Height", "Length"] {
+            named(&mut nbt, 2, name);
+            nbt.extend(if name == "Height" { 1i16 } else { 2i16 }.to_be_bytes());
+        }
+        named(&mut nbt, 10, "Palette");
+        named(&mut nbt, 3, "minecraft:air");
+        nbt.extend(0i32.to_be_bytes());
+        named(&mut nbt, 3, "minecraft:dirt[snowy=false]");
+        nbt.extend(1i32.to_be_bytes());
+        named(&mut nbt, 3, "minecraft:stone");
+        nbt.extend(2i32.to_be_bytes());
+        nbt.push(0);
+        named(&mut nbt, 7, "BlockData");
+        nbt.extend(4i32.to_be_bytes());
+        nbt.extend([1, 0, 2, 1]);
+        nbt.push(0);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&nbt).unwrap();
+        let bytes = encoder.finish().unwrap();
+
+        let registry = registry();
+        let dirt = Some(registry.block_id("dirt"));
+        let schematic = Schematic::import_sponge(&bytes, &registry).unwrap();
+
+        assert_eq!(schematic.iter().last().unwrap().0, BlockPos::new(1, 0, 1));
+        assert_eq!(
+            schematic.iter().map(|item| item.1).collect_vec(),
+            [dirt, None, None, dirt]
+        );
+    }
+}
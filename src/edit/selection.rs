@@ -0,0 +1,245 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+
+use crate::{
+    block_registry::{BlockId, SharedBlockRegistry},
+    config::Config,
+    player::{raycast_blocks, Player, PlayerCamera},
+    position::BlockPos,
+};
+
+use super::{Region, Schematic, WorldEdit, MAX_FILL_VOLUME, SCHEMATIC_EXTENSION};
+
+#[derive(Resource, Debug, Default)]
+pub struct Selection {
+    pub first: Option<BlockPos>,
+    pub second: Option<BlockPos>,
+    /// The block picked with the middle mouse button, used by fill, replace and hollow.
+    pub block: Option<BlockId>,
+}
+
+impl Selection {
+    pub fn region(&self) -> Option<Region> {
+        Some(Region::new(self.first?, self.second?))
+    }
+}
+
+#[derive(Resource, Debug, Default, Deref, DerefMut)]
+pub struct Clipboard(pub Option<Schematic>);
+
+/// The block the player's feet are in, which copies and pastes are anchored to.
+pub fn player_anchor(transform: &Transform) -> BlockPos {
+    BlockPos::from(transform.translation.floor())
+}
+
+pub(super) fn select_corners(
+    edit: WorldEdit,
+    mut selection: ResMut<Selection>,
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<&GlobalTransform, With<PlayerCamera>>,
+) {
+    let window = primary_window.single();
+
+    if window.cursor.grab_mode == CursorGrabMode::None {
+        return;
+    }
+
+    let controls = &edit.config.edit_controls;
+    let first = keyboard.just_pressed(controls.first_corner);
+    let second = keyboard.just_pressed(controls.second_corner);
+    let pick = mouse.just_pressed(MouseButton::Middle);
+
+    if !first && !second && !pick {
+        return;
+    }
+
+    let transform = camera.single();
+
+    let Ok((x, y, z)) = raycast_blocks(
+        edit.level(),
+        transform.translation(),
+        transform.forward(),
+        6,
+    ) else {
+        return;
+    };
+
+    let pos = BlockPos::new(x, y, z);
+
+    if first {
+        selection.first = Some(pos);
+    }
+
+    if second {
+        selection.second = Some(pos);
+    }
+
+    if pick {
+        selection.block = edit.level().block(pos).flatten();
+    }
+}
+
+pub(super) fn selection_controls(
+    mut edit: WorldEdit,
+    mut clipboard: ResMut<Clipboard>,
+    selection: Res<Selection>,
+    keyboard: Res<Input<KeyCode>>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    player: Query<&Transform, With<Player>>,
+    camera: Query<&GlobalTransform, With<PlayerCamera>>,
+) {
+    let window = primary_window.single();
+
+    if window.cursor.grab_mode == CursorGrabMode::None {
+        return;
+    }
+
+    let controls = edit.config.edit_controls.clone();
+    let anchor = player_anchor(player.single());
+    let modifier = keyboard.pressed(controls.modifier);
+
+    if modifier && keyboard.just_pressed(controls.paste) {
+        if let Some(schematic) = clipboard.0.as_ref() {
+            if within_limit(schematic.volume()) {
+                edit.paste(schematic, anchor);
+            }
+        }
+    } else if modifier && keyboard.just_pressed(controls.rotate) {
+        if let Some(schematic) = clipboard.0.as_mut() {
+            *schematic = schematic.rotate();
+        }
+    }
+
+    let Some(region) = selection.region() else {
+        return;
+    };
+
+    if keyboard.just_pressed(controls.clear) && within_limit(region.volume()) {
+        edit.fill(region, None);
+    }
+
+    if !modifier {
+        return;
+    }
+
+    let cut = keyboard.just_pressed(controls.cut);
+
+    if keyboard.just_pressed(controls.copy) || cut {
+        if within_limit(region.volume()) {
+            clipboard.0 = Some(edit.copy(region, anchor));
+
+            if cut {
+                edit.fill(region, None);
+            }
+        }
+    } else if keyboard.just_pressed(controls.fill) {
+        if within_limit(region.volume()) {
+            edit.fill(region, selection.block);
+        }
+    } else if keyboard.just_pressed(controls.hollow) {
+        if within_limit(region.volume()) {
+            edit.hollow(region, selection.block);
+        }
+    } else if keyboard.just_pressed(controls.replace) {
+        // Replaces every block matching the targeted one with the picked block.
+        let transform = camera.single();
+        let target = raycast_blocks(
+            edit.level(),
+            transform.translation(),
+            transform.forward(),
+            6,
+        )
+        .ok()
+        .and_then(|(x, y, z)| edit.level().block(BlockPos::new(x, y, z)));
+
+        if let Some(from) = target.filter(|_| within_limit(region.volume())) {
+            edit.replace(region, from, selection.block);
+        }
+    }
+}
+
+/// Whether an edit of `volume` blocks is small enough to make at once, as for `/fill`.
+fn within_limit(volume: i64) -> bool {
+    if volume > MAX_FILL_VOLUME {
+        warn!("Too many blocks to edit at once ({volume} > {MAX_FILL_VOLUME})");
+    }
+
+    volume <= MAX_FILL_VOLUME
+}
+
+/// Exports the clipboard to the schematic directory, and imports schematic files dropped onto
+/// the window into the clipboard.
+pub(super) fn clipboard_files(
+    mut clipboard: ResMut<Clipboard>,
+    mut dropped_files: EventReader<FileDragAndDrop>,
+    config: Res<Config>,
+    registry: Res<SharedBlockRegistry>,
+    keyboard: Res<Input<KeyCode>>,
+) {
+    let registry = registry.read().unwrap();
+
+    for event in dropped_files.iter() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = event else {
+            continue;
+        };
+
+        match Schematic::load(path_buf, &registry) {
+            Ok(schematic) => clipboard.0 = Some(schematic),
+            Err(error) => error!("could not import {}: {error}", path_buf.display()),
+        }
+    }
+
+    let controls = &config.edit_controls;
+
+    if !keyboard.pressed(controls.modifier) || !keyboard.just_pressed(controls.export) {
+        return;
+    }
+
+    let Some(schematic) = clipboard.0.as_ref() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path =
+        Path::new(&config.schematic_directory).join(format!("{timestamp}.{SCHEMATIC_EXTENSION}"));
+
+    match schematic.save(&path, &registry) {
+        Ok(()) => info!("exported clipboard to {}", path.display()),
+        Err(error) => error!("could not export {}: {error}", path.display()),
+    }
+}
+
+pub(super) fn draw_selection(mut gizmos: Gizmos, selection: Res<Selection>) {
+    for (corner, color) in [
+        (selection.first, Color::RED),
+        (selection.second, Color::BLUE),
+    ] {
+        if let Some(pos) = corner {
+            gizmos.cuboid(
+                Transform::from_translation(Vec3::from(pos) + Vec3::splat(0.5))
+                    .with_scale(Vec3::splat(1.02)),
+                color,
+            );
+        }
+    }
+
+    if let Some(region) = selection.region() {
+        gizmos.cuboid(
+            Transform::from_translation(region.center())
+                .with_scale(Vec3::from(region.size()) + Vec3::splat(0.04)),
+            Color::YELLOW,
+        );
+    }
+}
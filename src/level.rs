@@ -1,33 +1,60 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use bevy::{prelude::Resource, utils::HashMap};
 use noise::Perlin;
-use rusqlite::Connection;
 
-use crate::position::ChunkPos;
+use crate::{
+    block_registry::BlockId,
+    position::{BlockPos, ChunkPos},
+};
 
 mod chunk;
 mod chunk_builder;
+mod chunk_cache;
+mod compression;
 mod level_gen;
+mod palette;
+mod persistence;
+mod storage;
 
 pub use chunk::*;
 pub use chunk_builder::*;
+pub use chunk_cache::*;
+pub use compression::*;
 pub use level_gen::*;
+pub use palette::*;
+pub use persistence::*;
+pub use storage::*;
+
+/// Present while chunks are streamed from a server, which replaces local generation and saving.
+#[derive(Resource)]
+pub struct RemoteLevel;
 
 #[derive(Resource)]
 pub struct Level {
-    pub connection: Arc<Mutex<Connection>>,
+    /// Used only for reads; writes go through `writer`.
+    pub storage: Arc<dyn ChunkStorage>,
+    pub writer: WorldWriter,
     pub loaded_chunks: HashMap<ChunkPos, Chunk>,
     pub noise: Perlin,
 }
 
 impl Level {
+    pub fn new(storage: Arc<dyn ChunkStorage>, noise: Perlin) -> Self {
+        Self {
+            writer: WorldWriter::spawn(Arc::clone(&storage)),
+            storage,
+            loaded_chunks: HashMap::new(),
+            noise,
+        }
+    }
+
     pub fn add_chunk(&mut self, position: ChunkPos, chunk: Chunk) {
         self.loaded_chunks.insert(position, chunk);
     }
 
-    pub fn remove_chunk(&mut self, position: &ChunkPos) {
-        self.loaded_chunks.remove(position);
+    pub fn remove_chunk(&mut self, position: &ChunkPos) -> Option<Chunk> {
+        self.loaded_chunks.remove(position)
     }
 
     pub fn chunk(&self, position: ChunkPos) -> Option<&Chunk> {
@@ -38,6 +65,12 @@ impl Level {
         self.loaded_chunks.get_mut(&position)
     }
 
+    /// The block at `pos`, or `None` if its chunk isn't loaded.
+    pub fn block(&self, pos: BlockPos) -> Option<Option<BlockId>> {
+        let (chunk_pos, (x, y, z)) = pos.chunk_pos();
+        self.chunk(chunk_pos).map(|chunk| *chunk.block(x, y, z))
+    }
+
     pub fn noise(&self) -> Perlin {
         self.noise
     }
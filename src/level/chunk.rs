@@ -1,22 +1,32 @@
+use std::io;
+
 use bevy::prelude::Component;
-use indexmap::IndexSet;
 
 use crate::block_registry::{BlockId, BlockRegistry};
 
+use super::{decode_palette, encode_palette};
+
 #[derive(Component)]
 pub struct Dirty;
 
 pub const CHUNK_SIZE: usize = 32;
 
+/// The number of blocks in a chunk.
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
 #[derive(Clone)]
 pub struct Chunk {
     blocks: Vec<Option<BlockId>>,
+    /// Set by edits and cleared once the chunk is queued for saving. Chunks that were never
+    /// edited are regenerated from the seed instead of being stored.
+    modified: bool,
 }
 
 impl Default for Chunk {
     fn default() -> Self {
         Self {
             blocks: vec![None; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE],
+            modified: false,
         }
     }
 }
@@ -30,86 +40,118 @@ impl Chunk {
         &mut self.blocks[Self::index(x, y, z)]
     }
 
-    pub fn deserialize(bytes: &[u8], registry: &BlockRegistry) -> Chunk {
-        let mut chunk = Chunk::default();
-        let mut i = 0;
+    /// The block a lower detail mesh shows for the `scale`³ blocks from `(x, y, z)`: the most
+    /// common one, or air if most of them are air.
+    pub fn cell(&self, x: usize, y: usize, z: usize, scale: usize) -> Option<BlockId> {
+        if scale == 1 {
+            return *self.block(x, y, z);
+        }
 
-        let name_list_len = u16::from_be_bytes([bytes[i], bytes[i + 1]]) as usize;
-        i += 2;
+        let mut counts = Vec::<(BlockId, usize)>::new();
+
+        for dz in 0..scale {
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    let Some(block) = *self.block(x + dx, y + dy, z + dz) else {
+                        continue;
+                    };
+
+                    match counts.iter_mut().find(|(id, _)| *id == block) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((block, 1)),
+                    }
+                }
+            }
+        }
 
-        let mut names = Vec::with_capacity(name_list_len);
-        for _ in 0..name_list_len {
-            let name_len = bytes[i] as usize;
-            i += 1;
+        let solid = counts.iter().map(|(_, count)| count).sum::<usize>();
 
-            names.push(registry.block_id(&String::from_utf8_lossy(&bytes[i..i + name_len])));
-            i += name_len;
+        if solid * 2 < scale * scale * scale {
+            return None;
         }
 
-        let mut block = 0;
+        counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(id, _)| id)
+    }
 
-        while i < bytes.len() {
-            let count = u16::from_be_bytes([bytes[i], bytes[i + 1]]) as usize;
-            i += 2;
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
 
-            let index = u16::from_be_bytes([bytes[i], bytes[i + 1]]) as usize;
-            i += 2;
+    pub fn set_modified(&mut self, modified: bool) {
+        self.modified = modified;
+    }
 
-            for _ in 0..count {
-                if index == 0 {
-                    chunk.blocks[block] = None;
-                    block += 1;
-                } else {
-                    chunk.blocks[block] = Some(names[index - 1]);
-                    block += 1;
-                }
-            }
+    pub fn deserialize(bytes: &[u8], registry: &BlockRegistry) -> io::Result<Chunk> {
+        let (names, indices) = decode_palette(bytes, CHUNK_VOLUME)?;
+        let ids = names
+            .iter()
+            .map(|name| {
+                registry.get_block_id(name).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("unknown block {name}"))
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut chunk = Chunk::default();
+
+        for (block, index) in chunk.blocks.iter_mut().zip(indices) {
+            *block = index.checked_sub(1).map(|index| ids[index as usize]);
         }
 
-        chunk
+        Ok(chunk)
     }
 
     pub fn serialize(&self, registry: &BlockRegistry) -> Vec<u8> {
-        let mut data = Vec::new();
-        let mut names = IndexSet::new();
-        let mut last = None;
-        let mut count = 0;
-
-        for block in self.blocks.iter() {
-            let index = block
-                .map(|id| names.insert_full(registry.name(id)).0 + 1)
-                .unwrap_or_default();
-
-            if last == Some(index) {
-                count += 1;
-            } else {
-                if let Some(index) = last {
-                    data.extend((count as u16).to_be_bytes());
-                    data.extend((index as u16).to_be_bytes());
-                }
-                last = Some(index);
-                count = 1;
-            }
-        }
+        encode_palette(
+            self.blocks
+                .iter()
+                .map(|block| block.map(|id| registry.name(id))),
+        )
+    }
 
-        if let Some(index) = last {
-            data.extend((count as u16).to_be_bytes());
-            data.extend((index as u16).to_be_bytes());
-        }
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        x + y * CHUNK_SIZE + z * CHUNK_SIZE * CHUNK_SIZE
+    }
+}
 
-        let mut bytes = Vec::new();
-        bytes.extend((names.len() as u16).to_be_bytes());
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_registry;
 
-        for name in names {
-            bytes.push(name.len().try_into().unwrap());
-            bytes.extend(name.as_bytes());
+    use super::*;
+
+    #[test]
+    fn test_cell() {
+        let registry = test_registry(&["dirt", "stone"]);
+        let dirt = registry.block_id("dirt");
+        let stone = registry.block_id("stone");
+
+        let mut chunk = Chunk::default();
+        for x in 0..2 {
+            for z in 0..2 {
+                *chunk.block_mut(x, 0, z) = Some(dirt);
+            }
         }
+        *chunk.block_mut(0, 1, 0) = Some(stone);
 
-        bytes.extend(data);
-        bytes
+        assert_eq!(chunk.cell(0, 0, 0, 1), Some(dirt));
+        assert_eq!(chunk.cell(0, 1, 1, 1), None);
+        // Half solid counts as solid, and the most common block stands in for the rest.
+        assert_eq!(chunk.cell(0, 0, 0, 2), Some(dirt));
+        assert_eq!(chunk.cell(0, 0, 0, 4), None);
     }
 
-    fn index(x: usize, y: usize, z: usize) -> usize {
-        x + y * CHUNK_SIZE + z * CHUNK_SIZE * CHUNK_SIZE
+    #[test]
+    fn test_truncated() {
+        let registry = test_registry(&["dirt"]);
+        let mut chunk = Chunk::default();
+        *chunk.block_mut(1, 2, 3) = Some(registry.block_id("dirt"));
+
+        let bytes = chunk.serialize(&registry);
+        assert!(Chunk::deserialize(&bytes, &registry).is_ok());
+        assert!(Chunk::deserialize(&bytes[..bytes.len() - 4], &registry).is_err());
     }
 }
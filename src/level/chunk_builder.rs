@@ -5,10 +5,12 @@ use bevy::{
     render::{mesh, render_resource::PrimitiveTopology},
 };
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     block_registry::BlockRegistry,
     level::{Chunk, CHUNK_SIZE},
+    position::ChunkPos,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -49,6 +51,27 @@ impl ChunkBuilder {
         self.indices.extend(index.into_iter().map(|index| index.0));
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Scales every vertex, for meshes built from cells of several blocks.
+    pub fn scale(&mut self, factor: f32) {
+        for position in &mut self.positions {
+            *position = (Vec3::from(*position) * factor).into();
+        }
+    }
+
+    pub fn collider(&self) -> Collider {
+        let vertices = self.positions.iter().copied().map(Vec3::from).collect();
+        let indices = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        Collider::trimesh(vertices, indices)
+    }
+
     pub fn build(self) -> Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
@@ -68,17 +91,190 @@ pub struct AdjacentChunkData {
     pub back: Option<[[bool; CHUNK_SIZE]; CHUNK_SIZE]>,
 }
 
+impl AdjacentChunkData {
+    /// The solid cells facing a chunk at `pos` meshed at `scale`. `neighbour` returns the
+    /// adjacent chunks to hide faces against; a missing one leaves the faces on that side, which
+    /// also covers the gaps where a neighbour is meshed at another scale.
+    pub fn new<'a>(
+        pos: ChunkPos,
+        scale: usize,
+        neighbour: impl Fn(ChunkPos) -> Option<&'a Chunk>,
+    ) -> Self {
+        let face = |adjacent, axis, far| {
+            neighbour(adjacent).map(|chunk| boundary_cells(chunk, axis, far, scale))
+        };
+
+        Self {
+            left: face(pos - ChunkPos::X, 0, true),
+            right: face(pos + ChunkPos::X, 0, false),
+            top: face(pos + ChunkPos::Y, 1, false),
+            bottom: face(pos - ChunkPos::Y, 1, true),
+            front: face(pos + ChunkPos::Z, 2, false),
+            back: face(pos - ChunkPos::Z, 2, true),
+        }
+    }
+}
+
+/// Which cells are solid in the layer of a chunk on the face along `axis`, at the end of the
+/// axis if `far`. Indexed by the other two axes in order, in cells of `scale` blocks.
+fn boundary_cells(
+    chunk: &Chunk,
+    axis: usize,
+    far: bool,
+    scale: usize,
+) -> [[bool; CHUNK_SIZE]; CHUNK_SIZE] {
+    let cells = CHUNK_SIZE / scale;
+    let (row_axis, cell_axis) = match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    };
+
+    let mut data = [[false; CHUNK_SIZE]; CHUNK_SIZE];
+
+    for (row, data) in data.iter_mut().enumerate().take(cells) {
+        for (cell, data) in data.iter_mut().enumerate().take(cells) {
+            let mut pos = [0; 3];
+            pos[axis] = if far { CHUNK_SIZE - scale } else { 0 };
+            pos[row_axis] = row * scale;
+            pos[cell_axis] = cell * scale;
+            *data = chunk.cell(pos[0], pos[1], pos[2], scale).is_some();
+        }
+    }
+
+    data
+}
+
+/// The shape of the colliders built for chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColliderShape {
+    /// The faces of the chunk's mesh, matching what is drawn exactly.
+    #[default]
+    Trimesh,
+    /// Boxes merged from runs of solid blocks, which are cheaper to build and collide with.
+    Cuboids,
+}
+
+/// Meshes a chunk in cells of `scale`³ blocks, where a scale above one gives a lower detail mesh
+/// for distant chunks. `adjacent` must be read at the same scale.
 pub fn build_chunk(
     adjacent: AdjacentChunkData,
     chunk: Chunk,
     registry: Arc<RwLock<BlockRegistry>>,
-) -> (Mesh, Option<Collider>) {
+    scale: usize,
+) -> Mesh {
+    chunk_faces(adjacent, &chunk, &registry, scale).build()
+}
+
+/// Builds the collider of a chunk in full detail, or `None` if it is empty. `adjacent` is only
+/// used by trimeshes, to leave out the faces between chunks.
+pub fn build_collider(
+    adjacent: AdjacentChunkData,
+    chunk: Chunk,
+    registry: Arc<RwLock<BlockRegistry>>,
+    shape: ColliderShape,
+) -> Option<Collider> {
+    match shape {
+        ColliderShape::Trimesh => {
+            let chunk_builder = chunk_faces(adjacent, &chunk, &registry, 1);
+            (!chunk_builder.is_empty()).then(|| chunk_builder.collider())
+        }
+        ColliderShape::Cuboids => {
+            let cuboids = merged_cuboids(&chunk)
+                .into_iter()
+                .map(|(min, size)| {
+                    let half_size = size / 2.0;
+                    (
+                        min + half_size,
+                        Quat::IDENTITY,
+                        Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                    )
+                })
+                .collect::<Vec<_>>();
+            (!cuboids.is_empty()).then(|| Collider::compound(cuboids))
+        }
+    }
+}
+
+/// Covers the solid blocks of a chunk with boxes, each given by its lowest corner and size.
+/// Each box grows as far as it can along x, then z, then y, so a chunk that is solid throughout
+/// needs only one.
+fn merged_cuboids(chunk: &Chunk) -> Vec<(Vec3, Vec3)> {
+    let mut covered = vec![false; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+    let index = |x: usize, y: usize, z: usize| x + y * CHUNK_SIZE + z * CHUNK_SIZE * CHUNK_SIZE;
+    let mut cuboids = Vec::new();
+
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if covered[index(x, y, z)] || chunk.block(x, y, z).is_none() {
+                    continue;
+                }
+
+                let free = |x: usize, y: usize, z: usize| {
+                    !covered[index(x, y, z)] && chunk.block(x, y, z).is_some()
+                };
+
+                let mut end_x = x + 1;
+                while end_x < CHUNK_SIZE && free(end_x, y, z) {
+                    end_x += 1;
+                }
+
+                let mut end_z = z + 1;
+                while end_z < CHUNK_SIZE && (x..end_x).all(|x| free(x, y, end_z)) {
+                    end_z += 1;
+                }
+
+                let mut end_y = y + 1;
+                while end_y < CHUNK_SIZE
+                    && (z..end_z).all(|z| (x..end_x).all(|x| free(x, end_y, z)))
+                {
+                    end_y += 1;
+                }
+
+                for covered_y in y..end_y {
+                    for covered_z in z..end_z {
+                        for covered_x in x..end_x {
+                            covered[index(covered_x, covered_y, covered_z)] = true;
+                        }
+                    }
+                }
+
+                let min = Vec3::new(x as f32, y as f32, z as f32);
+                let max = Vec3::new(end_x as f32, end_y as f32, end_z as f32);
+                cuboids.push((min, max - min));
+            }
+        }
+    }
+
+    cuboids
+}
+
+/// Runs each block's render function on the cells of a chunk, scaled back up to blocks.
+fn chunk_faces(
+    adjacent: AdjacentChunkData,
+    chunk: &Chunk,
+    registry: &RwLock<BlockRegistry>,
+    scale: usize,
+) -> ChunkBuilder {
     let mut chunk_builder = ChunkBuilder::new();
+    let size = CHUNK_SIZE / scale;
 
-    for x in 0..CHUNK_SIZE {
-        for y in 0..CHUNK_SIZE {
-            for z in 0..CHUNK_SIZE {
-                let Some(block) = chunk.block(x, y, z) else {
+    let mut cells = Vec::with_capacity(size * size * size);
+    for z in 0..size {
+        for y in 0..size {
+            for x in 0..size {
+                cells.push(chunk.cell(x * scale, y * scale, z * scale, scale));
+            }
+        }
+    }
+    let cell = |x: usize, y: usize, z: usize| cells[x + y * size + z * size * size];
+    let registry = registry.read().unwrap();
+
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let Some(block) = cell(x, y, z) else {
                     continue;
                 };
 
@@ -86,49 +282,76 @@ pub fn build_chunk(
                     left: if x == 0 {
                         adjacent.left.map(|data| data[y][z]).unwrap_or(false)
                     } else {
-                        chunk.block(x - 1, y, z).is_some()
+                        cell(x - 1, y, z).is_some()
                     },
-                    right: if x == CHUNK_SIZE - 1 {
+                    right: if x == size - 1 {
                         adjacent.right.map(|data| data[y][z]).unwrap_or(false)
                     } else {
-                        chunk.block(x + 1, y, z).is_some()
+                        cell(x + 1, y, z).is_some()
                     },
                     bottom: if y == 0 {
                         adjacent.bottom.map(|data| data[x][z]).unwrap_or(false)
                     } else {
-                        chunk.block(x, y - 1, z).is_some()
+                        cell(x, y - 1, z).is_some()
                     },
-                    top: if y == CHUNK_SIZE - 1 {
+                    top: if y == size - 1 {
                         adjacent.top.map(|data| data[x][z]).unwrap_or(false)
                     } else {
-                        chunk.block(x, y + 1, z).is_some()
+                        cell(x, y + 1, z).is_some()
                     },
                     back: if z == 0 {
                         adjacent.back.map(|data| data[x][y]).unwrap_or(false)
                     } else {
-                        chunk.block(x, y, z - 1).is_some()
+                        cell(x, y, z - 1).is_some()
                     },
-                    front: if z == CHUNK_SIZE - 1 {
+                    front: if z == size - 1 {
                         adjacent.front.map(|data| data[x][y]).unwrap_or(false)
                     } else {
-                        chunk.block(x, y, z + 1).is_some()
+                        cell(x, y, z + 1).is_some()
                     },
                 };
 
                 let translation = Vec3::new(x as f32, y as f32, z as f32);
-                let lock = registry.read().unwrap();
-                let block = lock.block(*block);
+                let block = registry.block(block);
                 (block.render)(&mut chunk_builder, adjacent_sides, translation);
             }
         }
     }
 
-    let mesh = chunk_builder.build();
-    let mut collider = None;
+    chunk_builder.scale(scale as f32);
+    chunk_builder
+}
 
-    if mesh.count_vertices() > 0 {
-        collider = Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh);
-    }
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_registry;
 
-    (mesh, collider)
+    use super::*;
+
+    #[test]
+    fn test_merged_cuboids() {
+        let registry = test_registry(&["dirt"]);
+        let dirt = registry.block_id("dirt");
+
+        // A solid floor two blocks thick with one block on top.
+        let mut chunk = Chunk::default();
+        for y in 0..2 {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    *chunk.block_mut(x, y, z) = Some(dirt);
+                }
+            }
+        }
+        *chunk.block_mut(3, 2, 4) = Some(dirt);
+
+        let size = CHUNK_SIZE as f32;
+        assert_eq!(
+            merged_cuboids(&chunk),
+            vec![
+                (Vec3::ZERO, Vec3::new(size, 2.0, size)),
+                (Vec3::new(3.0, 2.0, 4.0), Vec3::ONE),
+            ]
+        );
+        assert!(merged_cuboids(&Chunk::default()).is_empty());
+    }
 }
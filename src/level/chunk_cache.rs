@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use bevy::{prelude::Resource, utils::HashMap};
+
+use crate::position::ChunkPos;
+
+use super::Chunk;
+
+/// Recently unloaded chunks, so walking back into an area doesn't load them from storage or
+/// generate them again. Chunks are saved before they are cached, so evicting one loses nothing.
+#[derive(Resource, Default)]
+pub struct ChunkCache {
+    chunks: HashMap<ChunkPos, (Chunk, u64)>,
+    /// Cached positions by when they were unloaded, oldest first.
+    order: BTreeMap<u64, ChunkPos>,
+    next: u64,
+}
+
+impl ChunkCache {
+    /// Caches an unloaded chunk, then evicts the longest cached chunks until at most `capacity`
+    /// remain.
+    pub fn insert(&mut self, pos: ChunkPos, chunk: Chunk, capacity: usize) {
+        if let Some((_, stamp)) = self.chunks.insert(pos, (chunk, self.next)) {
+            self.order.remove(&stamp);
+        }
+        self.order.insert(self.next, pos);
+        self.next += 1;
+
+        while self.chunks.len() > capacity {
+            let (_, pos) = self.order.pop_first().unwrap();
+            self.chunks.remove(&pos);
+        }
+    }
+
+    /// Removes a chunk from the cache to load it again.
+    pub fn take(&mut self, pos: ChunkPos) -> Option<Chunk> {
+        let (chunk, stamp) = self.chunks.remove(&pos)?;
+        self.order.remove(&stamp);
+        Some(chunk)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_oldest() {
+        let mut cache = ChunkCache::default();
+        let positions = (0..4).map(|x| ChunkPos::new(x, 0, 0)).collect::<Vec<_>>();
+
+        for &pos in &positions[..3] {
+            cache.insert(pos, Chunk::default(), 3);
+        }
+
+        // Unloading a chunk again makes it the newest.
+        cache.insert(positions[0], Chunk::default(), 3);
+        cache.insert(positions[3], Chunk::default(), 3);
+
+        assert_eq!(cache.len(), 3);
+        assert!(cache.take(positions[1]).is_none());
+        assert!(cache.take(positions[0]).is_some());
+        assert!(cache.take(positions[0]).is_none());
+
+        cache.insert(positions[1], Chunk::default(), 0);
+        assert!(cache.is_empty());
+    }
+}
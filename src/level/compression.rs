@@ -0,0 +1,193 @@
+use std::{
+    io::{self, Read},
+    sync::OnceLock,
+};
+
+use serde::{Deserialize, Serialize};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+use super::CHUNK_SIZE;
+
+/// Marks a chunk blob with a compression header. Uncompressed blobs start with the high byte of
+/// their palette's name count, which is never this large in practice.
+const MAGIC: u8 = 0xFF;
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// The largest valid serialized chunk: every block a run of its own, each with a palette entry
+/// of the longest name. Decompression stops here, but that only bounds the serialized data: its
+/// runs could still claim far more blocks, so `decode_palette` also checks the block count.
+const MAX_CHUNK_BYTES: usize =
+    2 + CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * (1 + u8::MAX as usize + 4);
+
+/// Trained on generated terrain by `write_dictionary` below. Blobs record which codec wrote them,
+/// so replacing the dictionary needs a new [`ChunkCompression`] variant rather than an edit.
+static DICTIONARY: &[u8] = include_bytes!("../../assets/chunk_dictionary.zdict");
+
+/// How chunk blobs are compressed, both in storage and in network packets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChunkCompression {
+    None,
+    Zstd,
+    #[default]
+    ZstdDictionary,
+}
+
+impl ChunkCompression {
+    pub const ALL: [ChunkCompression; 3] = [
+        ChunkCompression::None,
+        ChunkCompression::Zstd,
+        ChunkCompression::ZstdDictionary,
+    ];
+
+    /// The codec a blob from [`compress_chunk`] was written with, or `None` for blobs from
+    /// before compression existed.
+    pub fn of(blob: &[u8]) -> Option<Self> {
+        match blob {
+            [MAGIC, codec, ..] => Self::ALL.into_iter().find(|c| c.id() == *codec),
+            _ => None,
+        }
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            ChunkCompression::None => 0,
+            ChunkCompression::Zstd => 1,
+            ChunkCompression::ZstdDictionary => 2,
+        }
+    }
+}
+
+fn encoder_dictionary() -> &'static EncoderDictionary<'static> {
+    static DICTIONARY_CELL: OnceLock<EncoderDictionary> = OnceLock::new();
+    DICTIONARY_CELL.get_or_init(|| EncoderDictionary::copy(DICTIONARY, ZSTD_LEVEL))
+}
+
+fn decoder_dictionary() -> &'static DecoderDictionary<'static> {
+    static DICTIONARY_CELL: OnceLock<DecoderDictionary> = OnceLock::new();
+    DICTIONARY_CELL.get_or_init(|| DecoderDictionary::copy(DICTIONARY))
+}
+
+/// Wraps serialized chunk data in a header naming the codec, then compresses it.
+pub fn compress_chunk(bytes: &[u8], compression: ChunkCompression) -> Vec<u8> {
+    let mut blob = vec![MAGIC, compression.id()];
+
+    match compression {
+        ChunkCompression::None => blob.extend(bytes),
+        ChunkCompression::Zstd => {
+            zstd::stream::copy_encode(bytes, &mut blob, ZSTD_LEVEL).unwrap();
+        }
+        ChunkCompression::ZstdDictionary => {
+            let mut encoder =
+                zstd::Encoder::with_prepared_dictionary(&mut blob, encoder_dictionary()).unwrap();
+            io::copy(&mut &bytes[..], &mut encoder).unwrap();
+            encoder.finish().unwrap();
+        }
+    }
+
+    blob
+}
+
+/// Recovers serialized chunk data from a blob written by [`compress_chunk`], or returns blobs
+/// from before compression existed unchanged.
+pub fn decompress_chunk(blob: &[u8]) -> io::Result<Vec<u8>> {
+    let [MAGIC, codec, data @ ..] = blob else {
+        return Ok(blob.to_vec());
+    };
+
+    let mut bytes = Vec::new();
+    // A byte past the limit is enough to tell the data is too large.
+    let limit = MAX_CHUNK_BYTES as u64 + 1;
+
+    match *codec {
+        0 => bytes.extend(data),
+        1 => {
+            zstd::Decoder::new(data)?
+                .take(limit)
+                .read_to_end(&mut bytes)?;
+        }
+        2 => {
+            zstd::Decoder::with_prepared_dictionary(data, decoder_dictionary())?
+                .take(limit)
+                .read_to_end(&mut bytes)?;
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown chunk compression {codec}"),
+            ))
+        }
+    }
+
+    if bytes.len() > MAX_CHUNK_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunk data is too large",
+        ));
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use itertools::Itertools;
+    use noise::Perlin;
+
+    use crate::{
+        block_registry::BlockRegistry,
+        level::{generate_chunk, Chunk},
+        position::ChunkPos,
+        test_utils::test_registry,
+    };
+
+    use super::*;
+
+    fn terrain_samples() -> Vec<Vec<u8>> {
+        let registry = Arc::new(RwLock::new(test_registry(&["dirt"])));
+
+        (-4..4)
+            .cartesian_product(-1..2)
+            .cartesian_product(-4..4)
+            .map(|((x, y), z)| {
+                let chunk = generate_chunk(
+                    Perlin::default(),
+                    ChunkPos::new(x, y, z),
+                    Arc::clone(&registry),
+                );
+                chunk.serialize(&registry.read().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let legacy = Chunk::default().serialize(&BlockRegistry::default());
+        assert_eq!(decompress_chunk(&legacy).unwrap(), legacy);
+        assert_eq!(ChunkCompression::of(&legacy), None);
+
+        for bytes in terrain_samples().into_iter().take(8) {
+            for compression in ChunkCompression::ALL {
+                let blob = compress_chunk(&bytes, compression);
+                assert_eq!(decompress_chunk(&blob).unwrap(), bytes);
+                assert_eq!(ChunkCompression::of(&blob), Some(compression));
+            }
+        }
+
+        assert!(decompress_chunk(&[MAGIC, 9, 0]).is_err());
+
+        // Compresses to almost nothing, but expands past any real chunk.
+        let bomb = vec![0; MAX_CHUNK_BYTES + 1];
+        assert!(decompress_chunk(&compress_chunk(&bomb, ChunkCompression::Zstd)).is_err());
+    }
+
+    /// Retrains the bundled dictionary: `cargo test --release -- --ignored write_dictionary`.
+    #[test]
+    #[ignore]
+    fn write_dictionary() {
+        let dictionary = zstd::dict::from_samples(&terrain_samples(), 4096).unwrap();
+        std::fs::write("assets/chunk_dictionary.zdict", dictionary).unwrap();
+    }
+}
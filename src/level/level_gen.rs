@@ -1,101 +1,463 @@
-use std::sync::{Arc, Mutex, RwLock};
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{Arc, RwLock},
+};
 
 use async_io::block_on;
 use bevy::{
+    app::AppExit,
+    ecs::query::Has,
     prelude::*,
-    render::primitives::Aabb,
+    render::{
+        primitives::{Aabb, Frustum, Sphere},
+        renderer::RenderDevice,
+    },
     tasks::{AsyncComputeTaskPool, Task},
+    utils::{FloatOrd, HashMap},
 };
 use bevy_rapier3d::prelude::*;
 use futures_lite::future;
 use itertools::Itertools;
 use noise::{NoiseFn, Perlin};
-use rusqlite::Connection;
 
 use crate::{
     block_registry::{BlockRegistry, SharedBlockRegistry},
     config::Config,
-    level::{Chunk, Dirty, Level, CHUNK_SIZE},
-    player::Player,
+    level::{
+        compress_chunk, decompress_chunk, Chunk, ChunkCache, ChunkCompression, ChunkStorage, Dirty,
+        Level, RemoteLevel, WorldWriter, CHUNK_SIZE,
+    },
     position::{BlockPos, ChunkPos},
     ChunkMaterial,
 };
 
-use super::{build_chunk, AdjacentChunkData};
+use super::{build_chunk, build_collider, AdjacentChunkData, ColliderShape};
+
+#[derive(Component)]
+pub struct MeshTask(Task<Mesh>);
+
+#[derive(Component)]
+pub struct ColliderTask(Task<Option<Collider>>);
+
+/// Marks chunks near a dynamic body or a [`ColliderLoader`], which need a collider.
+#[derive(Component)]
+pub struct NeedsCollider;
+
+/// Marks a chunk whose collider was built and came out empty, so it isn't built again until the
+/// chunk changes.
+#[derive(Component)]
+pub struct EmptyCollider;
+
+/// Encodes a modified chunk and queues it with the [`WorldWriter`]. A chunk has at most one of
+/// these at a time, so its saves reach the writer in order.
+#[derive(Component)]
+pub struct SaveTask(Task<()>);
+
+/// Saves still running for chunks that have been unloaded. Loading one of these chunks again
+/// waits for its save first, so it sees the newest data.
+#[derive(Resource, Default)]
+pub struct UnloadedSaves(HashMap<ChunkPos, Task<()>>);
+
+/// The most chunks [`load_chunks`] starts loading per frame, so that crossing many chunks at
+/// once, such as by teleporting, is spread over several frames.
+const GENERATE_BUDGET: usize = 32;
+
+/// The highest block the terrain generator places. Chunks entirely above it are empty until
+/// edited, so they are never generated.
+pub const TERRAIN_HEIGHT_LIMIT: i32 = 18;
+
+/// The most mesh tasks [`generate_meshes`] starts per frame. Other dirty chunks wait, so the
+/// chunks that matter most aren't queued behind the rest.
+const MESH_BUDGET: usize = 32;
+
+/// How far the camera turns, in radians, before a loader's queue is sorted again.
+const RESORT_ANGLE: f32 = 0.5;
+
+/// The furthest render distance, in chunks, that can be asked for.
+pub const MAX_RENDER_DISTANCE: i32 = 64;
+
+/// How much further than the render distance chunks are unloaded, in chunks, so walking back
+/// and forth over a chunk border doesn't load and unload the same chunks.
+const UNLOAD_MARGIN: i32 = 2;
+
+/// Keeps the chunks within the render distance of this entity loaded.
+#[derive(Component)]
+pub struct ChunkLoader;
 
+/// Keeps colliders built around this entity even while it isn't a dynamic body, such as a
+/// player held in place until there is terrain to stand on.
 #[derive(Component)]
-pub struct MeshTask(Task<(Mesh, Option<Collider>)>);
+pub struct ColliderLoader;
+
+/// How far from a loader chunks are kept loaded, in chunks. Chunks are loaded in a cylinder,
+/// since there is far more to see horizontally than above or below.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RenderDistance {
+    pub horizontal: i32,
+    pub vertical: i32,
+}
+
+impl RenderDistance {
+    pub fn new(horizontal: i32, vertical: i32) -> Self {
+        Self {
+            horizontal,
+            vertical,
+        }
+    }
+}
+
+impl RenderDistance {
+    /// The distance beyond which loaded chunks are unloaded.
+    pub fn unload(self) -> Self {
+        Self::new(
+            self.horizontal + UNLOAD_MARGIN,
+            self.vertical + UNLOAD_MARGIN,
+        )
+    }
+}
+
+impl From<&Config> for RenderDistance {
+    fn from(config: &Config) -> Self {
+        Self::new(config.render_distance, config.vertical_render_distance)
+    }
+}
+
+/// The chunks a loader has yet to load, nearest first. Rebuilt only when the loader moves into
+/// another chunk or the render distance changes.
+#[derive(Component, Default)]
+struct LoadQueue {
+    center: Option<ChunkPos>,
+    distance: RenderDistance,
+    /// The camera direction the queue was sorted for.
+    forward: Option<Vec3>,
+    positions: VecDeque<ChunkPos>,
+}
+
+/// Counts of chunk loading and meshing tasks, to see where loading is slow.
+#[derive(Resource, Default, Debug)]
+pub struct TaskStats {
+    pub generate: TaskCounts,
+    pub mesh: TaskCounts,
+    pub collider: TaskCounts,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TaskCounts {
+    /// Waiting for a budget to start a task.
+    pub queued: usize,
+    pub in_flight: usize,
+    pub completed: u64,
+    /// Stopped before finishing, because the chunk unloaded or its task was replaced.
+    pub cancelled: u64,
+}
 
+/// [`chunk_offsets`] for each render distance in use, computed once per distance.
+#[derive(Resource, Default)]
+pub struct ChunkOffsets(HashMap<RenderDistance, Arc<[ChunkPos]>>);
+
+impl ChunkOffsets {
+    pub fn get(&mut self, distance: RenderDistance) -> Arc<[ChunkPos]> {
+        let offsets = self
+            .0
+            .entry(distance)
+            .or_insert_with(|| chunk_offsets(distance).into());
+        Arc::clone(offsets)
+    }
+}
+
+/// How many blocks along each side of a cell the chunk's current mesh was built from.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkLod(pub usize);
+
+/// The most blocks along each side of a cell in the lowest detail meshes.
+const MAX_LOD_SCALE: usize = 8;
+
+/// Loads or generates a chunk. A task that yields `None` couldn't produce its chunk, which is
+/// then unloaded, unless the task was replacing a chunk that is already loaded, which is kept.
 #[derive(Component)]
-pub struct GenerateTask(Task<Chunk>);
+pub struct GenerateTask(Task<Option<Chunk>>);
+
+impl GenerateTask {
+    pub fn new(task: Task<Option<Chunk>>) -> Self {
+        Self(task)
+    }
+}
+
+/// Finds the entity of a chunk by its position. Kept up to date by [`spawn_chunk`] and when
+/// chunks unload, so nothing has to scan every chunk entity to find one.
+#[derive(Resource, Default)]
+pub struct ChunkEntities(HashMap<ChunkPos, Entity>);
+
+impl ChunkEntities {
+    pub fn get(&self, pos: ChunkPos) -> Option<Entity> {
+        self.0.get(&pos).copied()
+    }
+
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        self.0.contains_key(&pos)
+    }
+
+    pub fn insert(&mut self, pos: ChunkPos, entity: Entity) {
+        self.0.insert(pos, entity);
+    }
+
+    pub fn remove(&mut self, pos: ChunkPos) -> Option<Entity> {
+        self.0.remove(&pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
 
 pub struct LevelGenPlugin;
 
 impl Plugin for LevelGenPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                (load_chunks, remove_chunks),
-                apply_deferred,
-                (add_chunks, generate_meshes, insert_meshes),
+        app.init_resource::<ChunkEntities>()
+            .init_resource::<ChunkOffsets>()
+            .init_resource::<ChunkCache>()
+            .init_resource::<UnloadedSaves>()
+            .init_resource::<TaskStats>()
+            .add_systems(
+                Update,
+                (
+                    (load_chunks, remove_chunks),
+                    apply_deferred,
+                    (
+                        add_chunks,
+                        update_lods,
+                        update_colliders,
+                        generate_meshes,
+                        insert_meshes,
+                        insert_colliders,
+                    ),
+                    (save_chunks, finish_saves),
+                    update_task_stats,
+                )
+                    .chain(),
             )
-                .chain(),
-        );
+            .add_systems(Last, save_on_exit);
     }
 }
 
-fn visible_chunk_positions(player_pos: Vec3, distance: i32) -> Vec<ChunkPos> {
-    let chunk_pos = BlockPos::from(player_pos).chunk_pos().0;
-    let center_pos = chunk_pos.center();
-    let block_distance = distance * CHUNK_SIZE as i32;
+/// Whether the chunk at `pos` is within the render distance of a loader in chunk `center`.
+pub fn in_render_distance(center: ChunkPos, pos: ChunkPos, distance: RenderDistance) -> bool {
+    let (dx, dz) = (pos.x - center.x, pos.z - center.z);
+    dx * dx + dz * dz <= distance.horizontal * distance.horizontal
+        && (pos.y - center.y).abs() <= distance.vertical
+}
 
-    (-distance..=distance)
-        .cartesian_product(-distance..=distance)
-        .cartesian_product(-distance..=distance)
-        .map(|((x, y), z)| chunk_pos + ChunkPos::new(x, y, z))
-        .filter(|pos| center_pos.distance(pos.center()) <= block_distance as f32)
-        .sorted_by(|a, b| {
-            a.center()
-                .distance(player_pos)
-                .total_cmp(&b.center().distance(player_pos))
-        })
+/// The offsets from a loader's chunk of every chunk within `distance`, nearest first.
+pub fn chunk_offsets(distance: RenderDistance) -> Vec<ChunkPos> {
+    let RenderDistance {
+        horizontal,
+        vertical,
+    } = distance;
+
+    (-horizontal..=horizontal)
+        .cartesian_product(-vertical..=vertical)
+        .cartesian_product(-horizontal..=horizontal)
+        .map(|((x, y), z)| ChunkPos::new(x, y, z))
+        .filter(|&offset| in_render_distance(ChunkPos::default(), offset, distance))
+        .sorted_by_key(|offset| offset.x * offset.x + offset.y * offset.y + offset.z * offset.z)
         .collect_vec()
 }
 
+/// How many blocks along each side of a cell to mesh the chunk at `pos` from, given the
+/// positions of the loaders. Chunks within `lod_distance` chunks are meshed in full detail, and
+/// the cells double in size each time the distance doubles.
+pub fn lod_scale(pos: ChunkPos, loaders: &[Vec3], lod_distance: i32) -> usize {
+    let distance = loaders
+        .iter()
+        .map(|&loader| loader.distance(pos.center()) / CHUNK_SIZE as f32)
+        .min_by(f32::total_cmp)
+        .unwrap_or(0.0);
+
+    let mut scale = 1;
+    let mut limit = lod_distance.max(1) as f32;
+
+    while distance > limit && scale < MAX_LOD_SCALE {
+        scale *= 2;
+        limit *= 2.0;
+    }
+
+    scale
+}
+
+/// Where the camera looks, so the chunks the player sees are loaded and meshed first.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraView {
+    pub forward: Vec3,
+    pub frustum: Frustum,
+}
+
+impl CameraView {
+    /// Whether any of the chunk at `pos` is in view, however far away.
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        let sphere = Sphere {
+            center: pos.center().into(),
+            radius: CHUNK_SIZE as f32 * 3f32.sqrt() / 2.0,
+        };
+        self.frustum.intersects_sphere(&sphere, false)
+    }
+}
+
+/// How soon the chunk at `pos` should be loaded or meshed for a loader at `origin`, lower
+/// being sooner. Chunks in front of the camera count as up to half as far away, and those in
+/// view as half as far again.
+pub fn chunk_priority(pos: ChunkPos, origin: Vec3, view: Option<&CameraView>) -> f32 {
+    let offset = pos.center() - origin;
+
+    let Some(view) = view else {
+        return offset.length();
+    };
+
+    let facing = view.forward.dot(offset.normalize_or_zero()).max(0.0);
+    let visible = if view.contains(pos) { 0.5 } else { 1.0 };
+    offset.length() * (1.0 - 0.5 * facing) * visible
+}
+
+/// The view of the 3D camera, if there is one.
+fn camera_view(camera: &Query<(&GlobalTransform, &Frustum), With<Camera3d>>) -> Option<CameraView> {
+    camera
+        .get_single()
+        .ok()
+        .map(|(transform, &frustum)| CameraView {
+            forward: transform.forward(),
+            frustum,
+        })
+}
+
+/// Spawns the entity for a chunk whose contents are produced by `task`.
+pub fn spawn_chunk(
+    commands: &mut Commands,
+    entities: &mut ChunkEntities,
+    pos: ChunkPos,
+    chunk_material: Option<&ChunkMaterial>,
+    task: Task<Option<Chunk>>,
+) -> Entity {
+    let mut entity = commands.spawn(pos);
+
+    if let Some(chunk_material) = chunk_material {
+        entity.insert(chunk_material.handle.clone());
+    }
+
+    entity
+        .insert(TransformBundle::from(Transform {
+            translation: BlockPos::from(pos).into(),
+            ..default()
+        }))
+        .insert(VisibilityBundle::default())
+        .insert(Friction::new(0.25))
+        .insert(Dirty)
+        .insert(GenerateTask(task));
+
+    let entity = entity.id();
+    entities.insert(pos, entity);
+    entity
+}
+
 fn load_chunks(
     mut commands: Commands,
     config: Res<Config>,
     level: Res<Level>,
-    chunk_material: Res<ChunkMaterial>,
+    remote: Option<Res<RemoteLevel>>,
+    chunk_material: Option<Res<ChunkMaterial>>,
     registry: Res<SharedBlockRegistry>,
-    chunks: Query<&ChunkPos>,
-    player: Query<&Transform, With<Player>>,
+    mut entities: ResMut<ChunkEntities>,
+    mut offsets: ResMut<ChunkOffsets>,
+    mut cache: ResMut<ChunkCache>,
+    mut saves: ResMut<UnloadedSaves>,
+    mut loaders: Query<(Entity, &Transform, Option<&mut LoadQueue>), With<ChunkLoader>>,
+    camera: Query<(&GlobalTransform, &Frustum), With<Camera3d>>,
 ) {
+    if remote.is_some() {
+        return;
+    }
+
     let thread_pool = AsyncComputeTaskPool::get();
-    let player_pos = player.single().translation;
+    let view = camera_view(&camera);
+    let forward = view.map(|view| view.forward);
+    let mut budget = GENERATE_BUDGET;
 
-    for pos in visible_chunk_positions(player_pos, config.render_distance)
-        .into_iter()
-        .filter(|pos| !chunks.iter().any(|existing| existing == pos))
-    {
-        let mut entity = commands.spawn(pos);
-
-        entity
-            .insert(chunk_material.handle.clone())
-            .insert(TransformBundle::from(Transform {
-                translation: BlockPos::from(pos).into(),
-                ..default()
-            }))
-            .insert(VisibilityBundle::default())
-            .insert(Friction::new(0.25))
-            .insert(Dirty);
+    for (entity, transform, queue) in loaders.iter_mut() {
+        let Some(mut queue) = queue else {
+            commands.entity(entity).insert(LoadQueue::default());
+            continue;
+        };
 
-        let registry = Arc::clone(&registry);
-        let connection = Arc::clone(&level.connection);
-        let task = thread_pool.spawn(load_chunk(pos, level.noise(), registry, connection));
+        let center = BlockPos::from(transform.translation).chunk_pos().0;
+        let distance = RenderDistance::from(&*config);
+
+        let priority =
+            |&pos: &ChunkPos| FloatOrd(chunk_priority(pos, transform.translation, view.as_ref()));
 
-        entity.insert(GenerateTask(task));
+        if queue.center != Some(center) || queue.distance != distance {
+            queue.positions = offsets
+                .get(distance)
+                .iter()
+                .map(|&offset| center + offset)
+                .filter(|&pos| !entities.contains(pos))
+                .sorted_by_cached_key(priority)
+                .collect();
+            queue.center = Some(center);
+            queue.distance = distance;
+            queue.forward = forward;
+        } else if forward.is_some_and(|forward| {
+            queue
+                .forward
+                .is_none_or(|sorted| sorted.angle_between(forward) > RESORT_ANGLE)
+        }) {
+            // Turning brings other chunks into view, so they go first.
+            queue
+                .positions
+                .make_contiguous()
+                .sort_by_cached_key(priority);
+            queue.forward = forward;
+        }
+
+        while budget > 0 {
+            let Some(pos) = queue.positions.pop_front() else {
+                break;
+            };
+
+            // Another loader may have got to it first.
+            if entities.contains(pos) {
+                continue;
+            }
+
+            let task = if let Some(chunk) = cache.take(pos) {
+                thread_pool.spawn(async move { Some(chunk) })
+            } else {
+                let noise = level.noise();
+                let registry = Arc::clone(&registry);
+                let storage = Arc::clone(&level.storage);
+                let writer = level.writer.clone();
+                let save = saves.0.remove(&pos);
+                thread_pool.spawn(async move {
+                    if let Some(save) = save {
+                        save.await;
+                    }
+
+                    Some(load_chunk(pos, noise, registry, storage, writer).await)
+                })
+            };
+
+            spawn_chunk(
+                &mut commands,
+                &mut entities,
+                pos,
+                chunk_material.as_deref(),
+                task,
+            );
+            budget -= 1;
+        }
     }
 }
 
@@ -103,37 +465,50 @@ async fn load_chunk(
     pos: ChunkPos,
     noise: Perlin,
     registry: Arc<RwLock<BlockRegistry>>,
-    connection: Arc<Mutex<Connection>>,
+    storage: Arc<dyn ChunkStorage>,
+    writer: WorldWriter,
 ) -> Chunk {
-    let result = connection.lock().unwrap().query_row(
-        "SELECT `data` FROM `chunks` WHERE `x` = ?1 AND `y` = ?2 AND `z` = ?3",
-        (pos.x, pos.y, pos.z),
-        |row| row.get::<_, Vec<u8>>(0),
-    );
-
-    if let Ok(bytes) = result {
-        Chunk::deserialize(&bytes, &registry.read().unwrap())
-    } else {
+    // A chunk saved moments ago may still be waiting to be written.
+    let blob = match writer.pending(pos) {
+        Some(blob) => Ok(Some(blob)),
+        None => storage.load_chunk(pos),
+    };
+
+    let chunk = match blob {
+        Ok(Some(blob)) => decompress_chunk(&blob)
+            .and_then(|bytes| Chunk::deserialize(&bytes, &registry.read().unwrap())),
+        Ok(None) => return generate_chunk(noise, pos, registry),
+        Err(err) => Err(err),
+    };
+
+    // The stored blob is left alone, so it is only replaced if the new chunk is edited.
+    chunk.unwrap_or_else(|err| {
+        warn!("Regenerating unreadable chunk {pos:?}: {err}");
         generate_chunk(noise, pos, registry)
-    }
+    })
 }
 
-fn generate_chunk(
+pub fn generate_chunk(
     noise: Perlin,
     chunk_pos: ChunkPos,
     registry: Arc<RwLock<BlockRegistry>>,
 ) -> Chunk {
-    let dirt = registry.read().unwrap().block_id("dirt");
     let mut chunk = Chunk::default();
 
+    if chunk_pos.y * CHUNK_SIZE as i32 > TERRAIN_HEIGHT_LIMIT {
+        return chunk;
+    }
+
+    let dirt = registry.read().unwrap().block_id("dirt");
+
     for x in 0..CHUNK_SIZE {
         for z in 0..CHUNK_SIZE {
             let block_x = chunk_pos.x * CHUNK_SIZE as i32 + x as i32;
             let block_z = chunk_pos.z * CHUNK_SIZE as i32 + z as i32;
-            let noise = noise.get([block_x as f64 / 90.0, block_z as f64 / 90.0]);
+            let height = terrain_height(noise, block_x, block_z);
             for y in 0..CHUNK_SIZE {
                 let block_y = chunk_pos.y * CHUNK_SIZE as i32 + y as i32;
-                if block_y as f64 <= noise * 18.0 {
+                if block_y <= height {
                     *chunk.block_mut(x, y, z) = Some(dirt);
                 }
             }
@@ -143,27 +518,48 @@ fn generate_chunk(
     chunk
 }
 
+/// The height of the highest generated block in a column, before any edits.
+pub fn terrain_height(noise: Perlin, x: i32, z: i32) -> i32 {
+    let noise = noise.get([x as f64 / 90.0, z as f64 / 90.0]);
+    (noise * TERRAIN_HEIGHT_LIMIT as f64).floor() as i32
+}
+
 fn add_chunks(
     mut commands: Commands,
     mut level: ResMut<Level>,
+    mut entities: ResMut<ChunkEntities>,
+    mut stats: ResMut<TaskStats>,
     mut loading_chunks: Query<(Entity, &ChunkPos, &mut GenerateTask)>,
-    chunks: Query<(Entity, &ChunkPos), (Without<GenerateTask>, Without<Dirty>)>,
+    chunks: Query<(), (Without<GenerateTask>, Without<Dirty>)>,
 ) {
     for (entity, &pos, mut generate_task) in loading_chunks.iter_mut() {
         let Some(chunk) = block_on(future::poll_once(&mut generate_task.0)) else {
             continue;
         };
 
+        let Some(chunk) = chunk else {
+            if level.chunk(pos).is_some() {
+                commands.entity(entity).remove::<GenerateTask>();
+            } else {
+                commands.entity(entity).despawn_recursive();
+                entities.remove(pos);
+            }
+            continue;
+        };
+
         let mut entity = commands.entity(entity);
         entity.remove::<GenerateTask>();
         level.add_chunk(pos, chunk);
+        stats.generate.completed += 1;
 
-        for (adjacent, &adjacent_pos) in chunks.iter() {
-            if !pos.is_adjacent(adjacent_pos) {
-                continue;
+        for adjacent in pos
+            .neighbours()
+            .into_iter()
+            .filter_map(|pos| entities.get(pos))
+        {
+            if chunks.contains(adjacent) {
+                commands.entity(adjacent).insert(Dirty);
             }
-
-            commands.entity(adjacent).insert(Dirty);
         }
     }
 }
@@ -172,37 +568,144 @@ fn remove_chunks(
     mut commands: Commands,
     mut level: ResMut<Level>,
     config: Res<Config>,
-    chunks: Query<(Entity, &ChunkPos)>,
-    player: Query<&Transform, With<Player>>,
+    registry: Res<SharedBlockRegistry>,
+    remote: Option<Res<RemoteLevel>>,
+    mut entities: ResMut<ChunkEntities>,
+    mut cache: ResMut<ChunkCache>,
+    mut saves: ResMut<UnloadedSaves>,
+    mut stats: ResMut<TaskStats>,
+    mut chunks: Query<(
+        Entity,
+        &ChunkPos,
+        Option<&mut SaveTask>,
+        Has<GenerateTask>,
+        Has<MeshTask>,
+        Has<ColliderTask>,
+    )>,
+    loaders: Query<&Transform, With<ChunkLoader>>,
 ) {
-    let max_distance = config.render_distance * CHUNK_SIZE as i32;
-    let transform = player.single();
-    let player_chunk_pos = BlockPos::from(transform.translation).chunk_pos().0;
-    let player_center_pos = player_chunk_pos.center();
+    let distance = RenderDistance::from(&*config);
+    let loader_positions = loaders
+        .iter()
+        .map(|transform| BlockPos::from(transform.translation).chunk_pos().0)
+        .collect_vec();
+    let out_of_range = |pos, distance| {
+        loader_positions
+            .iter()
+            .all(|&center| !in_render_distance(center, pos, distance))
+    };
+    let thread_pool = AsyncComputeTaskPool::get();
+
+    for (chunk, &chunk_pos, save_task, generate_task, mesh_task, collider_task) in chunks.iter_mut()
+    {
+        // A chunk still loading has nothing to keep, so it is stopped as soon as it leaves the
+        // render distance rather than the unload distance. Remote chunks are only sent once.
+        let cancel = generate_task && remote.is_none() && out_of_range(chunk_pos, distance);
+
+        if cancel || out_of_range(chunk_pos, distance.unload()) {
+            // Generate and mesh tasks are cancelled by despawning the entity, which drops them
+            // before the thread pool runs them any further.
+            stats.generate.cancelled += generate_task as u64;
+            stats.mesh.cancelled += mesh_task as u64;
+            stats.collider.cancelled += collider_task as u64;
+
+            // Dropping the task would cancel it, so it is taken out of the entity to finish.
+            let previous = save_task
+                .map(|mut save_task| mem::replace(&mut save_task.0, thread_pool.spawn(async {})));
 
-    for (chunk, chunk_pos) in chunks.iter() {
-        if player_center_pos.distance(chunk_pos.center()) > max_distance as f32 {
             commands.entity(chunk).despawn_recursive();
-            level.remove_chunk(chunk_pos);
+            entities.remove(chunk_pos);
+
+            // A server may change remote chunks while they are unloaded.
+            let Some(mut chunk) = level.remove_chunk(&chunk_pos).filter(|_| remote.is_none())
+            else {
+                continue;
+            };
+
+            // Encoded off the main thread, after any earlier save so that it lands last.
+            let save = if chunk.is_modified() {
+                chunk.set_modified(false);
+                let save = save_chunk(
+                    chunk_pos,
+                    chunk.clone(),
+                    Arc::clone(&registry),
+                    level.writer.clone(),
+                    config.chunk_compression,
+                );
+
+                Some(thread_pool.spawn(async move {
+                    if let Some(previous) = previous {
+                        previous.await;
+                    }
+
+                    save.await;
+                }))
+            } else {
+                previous
+            };
+
+            if let Some(save) = save {
+                saves.0.insert(chunk_pos, save);
+            }
+
+            cache.insert(chunk_pos, chunk, config.chunk_cache_size);
+        }
+    }
+}
+
+/// Remeshes chunks whose level of detail changed as the loaders moved, along with their
+/// neighbours, since the seams between them depend on it.
+fn update_lods(
+    mut commands: Commands,
+    config: Res<Config>,
+    entities: Res<ChunkEntities>,
+    loaders: Query<&Transform, With<ChunkLoader>>,
+    chunks: Query<(&ChunkPos, &ChunkLod)>,
+    mut last: Local<(Vec<ChunkPos>, i32)>,
+) {
+    let loader_chunks = loaders
+        .iter()
+        .map(|transform| BlockPos::from(transform.translation).chunk_pos().0)
+        .collect_vec();
+
+    if *last == (loader_chunks.clone(), config.lod_distance) {
+        return;
+    }
+    *last = (loader_chunks, config.lod_distance);
+
+    let loader_positions = loaders
+        .iter()
+        .map(|transform| transform.translation)
+        .collect_vec();
+
+    for (&pos, &ChunkLod(scale)) in chunks.iter() {
+        if lod_scale(pos, &loader_positions, config.lod_distance) == scale {
+            continue;
+        }
+
+        for entity in std::iter::once(pos)
+            .chain(pos.neighbours())
+            .filter_map(|pos| entities.get(pos))
+        {
+            commands.entity(entity).insert(Dirty);
         }
     }
 }
 
 fn insert_meshes(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut meshes: Option<ResMut<Assets<Mesh>>>,
+    mut stats: ResMut<TaskStats>,
     mut query: Query<(Entity, &mut MeshTask)>,
 ) {
     for (entity, mut mesh_task) in query.iter_mut() {
-        if let Some((mesh, collider)) = block_on(future::poll_once(&mut mesh_task.0)) {
+        if let Some(mesh) = block_on(future::poll_once(&mut mesh_task.0)) {
             let mut entity = commands.entity(entity);
             entity.remove::<MeshTask>();
-            entity.insert(meshes.add(mesh)).remove::<Aabb>();
+            stats.mesh.completed += 1;
 
-            if let Some(collider) = collider {
-                entity.insert(collider);
-            } else {
-                entity.remove::<Collider>();
+            if let Some(meshes) = meshes.as_mut() {
+                entity.insert(meshes.add(mesh)).remove::<Aabb>();
             }
         }
     }
@@ -212,85 +715,573 @@ fn generate_meshes(
     mut commands: Commands,
     registry: Res<SharedBlockRegistry>,
     level: Res<Level>,
-    query: Query<(Entity, &ChunkPos), With<Dirty>>,
+    config: Res<Config>,
+    render_device: Option<Res<RenderDevice>>,
+    mut stats: ResMut<TaskStats>,
+    query: Query<
+        (
+            Entity,
+            &ChunkPos,
+            Has<MeshTask>,
+            Has<ColliderTask>,
+            Has<NeedsCollider>,
+        ),
+        With<Dirty>,
+    >,
+    loaders: Query<&Transform, With<ChunkLoader>>,
+    camera: Query<(&GlobalTransform, &Frustum), With<Camera3d>>,
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
+    // Without a renderer only the colliders are built.
+    let render = render_device.is_some();
+    let view = camera_view(&camera);
+    let loader_positions = loaders
+        .iter()
+        .map(|transform| transform.translation)
+        .collect_vec();
 
-    for (entity, &pos) in query.iter() {
-        let Some(chunk) = level.chunk(pos).cloned() else {
-            continue;
-        };
+    let priority = |pos: ChunkPos| {
+        loaders
+            .iter()
+            .map(|transform| FloatOrd(chunk_priority(pos, transform.translation, view.as_ref())))
+            .min()
+            .unwrap_or(FloatOrd(0.0))
+    };
 
+    // Chunks that are still loading can't be meshed yet. Chunks with bodies on them go first,
+    // so nothing falls through an edited chunk.
+    let dirty = query
+        .iter()
+        .filter(|(_, &pos, ..)| level.chunk(pos).is_some())
+        .sorted_by_cached_key(|&(_, &pos, .., needs_collider)| (!needs_collider, priority(pos)))
+        .take(MESH_BUDGET)
+        .collect_vec();
+
+    for (entity, &pos, has_mesh_task, has_collider_task, needs_collider) in dirty {
         let Some(mut entity) = commands.get_entity(entity) else {
             continue;
         };
 
-        macro_rules! adjacent_faces {
-            ( $main:ident, $( $name:ident, $pos:expr, |$row_name:ident, $cell_name:ident|
-                    => [$x:expr, $y:expr, $z:expr]; )* ) => {
-                $( let $name = level.chunk($pos).map(|chunk| {
-                    let mut data = [[false; CHUNK_SIZE]; CHUNK_SIZE];
-                    for ($row_name, data) in data.iter_mut().enumerate() {
-                        for ($cell_name, data) in data.iter_mut().enumerate() {
-                            *data = chunk.block($x, $y, $z).is_some();
-                        }
-                    }
-                    data
-                }); )*
+        let scale = lod_scale(pos, &loader_positions, config.lod_distance);
+        entity.remove::<Dirty>().insert(ChunkLod(scale));
 
-                let $main = AdjacentChunkData { $( $name, )* };
-            };
+        if render {
+            // The old mesh would be out of date, so it is replaced.
+            stats.mesh.cancelled += has_mesh_task as u64;
+
+            // Faces against a neighbour meshed at another scale are kept to cover the seam.
+            let adjacent = AdjacentChunkData::new(pos, scale, |adjacent| {
+                level.chunk(adjacent).filter(|_| {
+                    lod_scale(adjacent, &loader_positions, config.lod_distance) == scale
+                })
+            });
+
+            let chunk = level.chunk(pos).unwrap().clone();
+            let registry = Arc::clone(&registry);
+            let task =
+                thread_pool.spawn(async move { build_chunk(adjacent, chunk, registry, scale) });
+            entity.insert(MeshTask(task));
+        }
+
+        if needs_collider {
+            stats.collider.cancelled += has_collider_task as u64;
+            let task = spawn_collider(&level, pos, &registry, config.collider_shape);
+            entity.insert(ColliderTask(task));
+        }
+    }
+}
+
+fn spawn_collider(
+    level: &Level,
+    pos: ChunkPos,
+    registry: &SharedBlockRegistry,
+    shape: ColliderShape,
+) -> Task<Option<Collider>> {
+    let adjacent = AdjacentChunkData::new(pos, 1, |adjacent| level.chunk(adjacent));
+    let chunk = level.chunk(pos).unwrap().clone();
+    let registry = Arc::clone(registry);
+
+    AsyncComputeTaskPool::get()
+        .spawn(async move { build_collider(adjacent, chunk, registry, shape) })
+}
+
+/// Gives colliders to the chunks around dynamic bodies, and takes them away from chunks no
+/// body is near, since building them is the slowest part of meshing.
+pub fn update_colliders(
+    mut commands: Commands,
+    config: Res<Config>,
+    level: Res<Level>,
+    registry: Res<SharedBlockRegistry>,
+    entities: Res<ChunkEntities>,
+    mut stats: ResMut<TaskStats>,
+    bodies: Query<(&GlobalTransform, &RigidBody)>,
+    loaders: Query<&GlobalTransform, With<ColliderLoader>>,
+    unmarked: Query<(), Without<NeedsCollider>>,
+    marked: Query<(Entity, &ChunkPos, Has<ColliderTask>), With<NeedsCollider>>,
+    missing: Query<
+        (Entity, &ChunkPos),
+        (
+            With<NeedsCollider>,
+            Without<Collider>,
+            Without<EmptyCollider>,
+            Without<ColliderTask>,
+            Without<Dirty>,
+        ),
+    >,
+    changed: Query<Entity, (With<EmptyCollider>, With<Dirty>)>,
+) {
+    // An edited chunk may not be empty anymore.
+    for entity in changed.iter() {
+        commands.entity(entity).remove::<EmptyCollider>();
+    }
+
+    let body_chunks = bodies
+        .iter()
+        .filter(|(_, &body)| body == RigidBody::Dynamic)
+        .map(|(transform, _)| transform)
+        .chain(loaders.iter())
+        .map(|transform| BlockPos::from(transform.translation()).chunk_pos().0)
+        .collect_vec();
+
+    // Colliders are kept a chunk further than they are added, so a body moving back and forth
+    // doesn't rebuild them.
+    // A body at the edge of its chunk can touch the next one, so that always has a collider.
+    let distance = config.collider_distance.max(1);
+    let within = |pos: ChunkPos, distance: i32| {
+        body_chunks.iter().any(|&body| {
+            (pos.x - body.x).abs() <= distance
+                && (pos.y - body.y).abs() <= distance
+                && (pos.z - body.z).abs() <= distance
+        })
+    };
+
+    for (entity, &pos, has_collider_task) in marked.iter() {
+        if !within(pos, distance + 1) {
+            stats.collider.cancelled += has_collider_task as u64;
+            commands
+                .entity(entity)
+                .remove::<(NeedsCollider, Collider, EmptyCollider, ColliderTask)>();
+        }
+    }
+
+    for &body in &body_chunks {
+        for offset in (-distance..=distance)
+            .cartesian_product(-distance..=distance)
+            .cartesian_product(-distance..=distance)
+            .map(|((x, y), z)| ChunkPos::new(x, y, z))
+        {
+            if let Some(entity) = entities
+                .get(body + offset)
+                .filter(|&e| unmarked.contains(e))
+            {
+                commands.entity(entity).insert(NeedsCollider);
+            }
         }
+    }
+
+    // Chunks marked while dirty get their collider when they are meshed.
+    for (entity, &pos) in missing.iter() {
+        if level.chunk(pos).is_some() {
+            let task = spawn_collider(&level, pos, &registry, config.collider_shape);
+            commands.entity(entity).insert(ColliderTask(task));
+        }
+    }
+}
 
-        adjacent_faces!(adjacent,
-            left, pos - ChunkPos::X, |y, z| => [CHUNK_SIZE - 1, y, z];
-            right, pos + ChunkPos::X, |y, z| => [0, y, z];
-            top, pos + ChunkPos::Y, |x, z| => [x, 0, z];
-            bottom, pos - ChunkPos::Y, |x, z| => [x, CHUNK_SIZE - 1, z];
-            front, pos + ChunkPos::Z, |x, y| => [x, y, 0];
-            back, pos - ChunkPos::Z, |x, y| => [x, y, CHUNK_SIZE - 1];
-        );
+/// Gives chunks the colliders their [`ColliderTask`]s built.
+pub fn insert_colliders(
+    mut commands: Commands,
+    mut stats: ResMut<TaskStats>,
+    mut query: Query<(Entity, &mut ColliderTask)>,
+) {
+    for (entity, mut collider_task) in query.iter_mut() {
+        if let Some(collider) = block_on(future::poll_once(&mut collider_task.0)) {
+            let mut entity = commands.entity(entity);
+            entity.remove::<ColliderTask>();
+            stats.collider.completed += 1;
+
+            if let Some(collider) = collider {
+                entity.insert(collider).remove::<EmptyCollider>();
+            } else {
+                entity.remove::<Collider>().insert(EmptyCollider);
+            }
+        }
+    }
+}
 
+fn save_chunks(
+    mut commands: Commands,
+    registry: Res<SharedBlockRegistry>,
+    mut level: ResMut<Level>,
+    config: Res<Config>,
+    remote: Option<Res<RemoteLevel>>,
+    mut saves: ResMut<UnloadedSaves>,
+    query: Query<(Entity, &ChunkPos), Without<SaveTask>>,
+) {
+    // Chunks streamed from a server belong to its world rather than the local one.
+    if remote.is_some() {
+        return;
+    }
+
+    let thread_pool = AsyncComputeTaskPool::get();
+
+    for (entity, &pos) in query.iter() {
+        // Untouched terrain is regenerated from the seed.
+        let Some(chunk) = level.chunk_mut(pos).filter(|chunk| chunk.is_modified()) else {
+            continue;
+        };
+
+        chunk.set_modified(false);
+        let chunk = chunk.clone();
         let registry = Arc::clone(&registry);
-        let connection = Arc::clone(&level.connection);
-        let task = thread_pool.spawn(save_chunk(pos, chunk, adjacent, registry, connection));
+        let writer = level.writer.clone();
+        let save = save_chunk(pos, chunk, registry, writer, config.chunk_compression);
+        // A chunk reloaded from the cache may still have a save running from when it unloaded.
+        let previous = saves.0.remove(&pos);
+        let task = thread_pool.spawn(async move {
+            if let Some(previous) = previous {
+                previous.await;
+            }
+
+            save.await;
+        });
 
-        entity.remove::<Dirty>().insert(MeshTask(task));
+        commands.entity(entity).insert(SaveTask(task));
     }
 }
 
 async fn save_chunk(
     pos: ChunkPos,
     chunk: Chunk,
-    adjacent: AdjacentChunkData,
     registry: Arc<RwLock<BlockRegistry>>,
-    connection: Arc<Mutex<Connection>>,
-) -> (Mesh, Option<Collider>) {
-    let data = chunk.serialize(&registry.read().unwrap());
-    let conn = connection.lock().unwrap();
-
-    if conn
-        .query_row(
-            "SELECT COUNT(*) FROM `chunks` WHERE `x` = ?1 AND `y` = ?2 AND `z` = ?3",
-            (pos.x, pos.y, pos.z),
-            |row| row.get::<_, usize>(0),
-        )
-        .unwrap()
-        == 0
-    {
-        conn.execute(
-            "INSERT INTO `chunks` (`x`, `y`, `z`, `data`) VALUES (?1, ?2, ?3, ?4)",
-            (pos.x, pos.y, pos.z, data),
-        )
-        .unwrap();
-    } else {
-        conn.execute(
-            "UPDATE `chunks` SET `data` = ?1 WHERE `x` = ?2 AND `y` = ?3 AND `z` = ?4",
-            (data, pos.x, pos.y, pos.z),
+    writer: WorldWriter,
+    compression: ChunkCompression,
+) {
+    let data = compress_chunk(&chunk.serialize(&registry.read().unwrap()), compression);
+    writer.write(pos, data);
+}
+
+/// Queues `pos` with the writer on the current thread if it has unsaved edits.
+fn save_now(
+    level: &mut Level,
+    pos: ChunkPos,
+    registry: &RwLock<BlockRegistry>,
+    compression: ChunkCompression,
+) {
+    let Some(chunk) = level.chunk_mut(pos).filter(|chunk| chunk.is_modified()) else {
+        return;
+    };
+
+    chunk.set_modified(false);
+    let data = compress_chunk(&chunk.serialize(&registry.read().unwrap()), compression);
+    level.writer.write(pos, data);
+}
+
+fn update_task_stats(
+    mut stats: ResMut<TaskStats>,
+    level: Res<Level>,
+    queues: Query<&LoadQueue>,
+    generate_tasks: Query<(), With<GenerateTask>>,
+    mesh_tasks: Query<(), With<MeshTask>>,
+    collider_tasks: Query<(), With<ColliderTask>>,
+    dirty: Query<&ChunkPos, With<Dirty>>,
+    missing_colliders: Query<
+        (),
+        (
+            With<NeedsCollider>,
+            Without<Collider>,
+            Without<EmptyCollider>,
+            Without<ColliderTask>,
+        ),
+    >,
+) {
+    stats.generate.queued = queues.iter().map(|queue| queue.positions.len()).sum();
+    stats.generate.in_flight = generate_tasks.iter().count();
+    stats.mesh.queued = dirty
+        .iter()
+        .filter(|&&pos| level.chunk(pos).is_some())
+        .count();
+    stats.mesh.in_flight = mesh_tasks.iter().count();
+    stats.collider.queued = missing_colliders.iter().count();
+    stats.collider.in_flight = collider_tasks.iter().count();
+}
+
+fn finish_saves(
+    mut commands: Commands,
+    mut saves: ResMut<UnloadedSaves>,
+    mut query: Query<(Entity, &mut SaveTask)>,
+) {
+    for (entity, mut save_task) in query.iter_mut() {
+        if block_on(future::poll_once(&mut save_task.0)).is_some() {
+            commands.entity(entity).remove::<SaveTask>();
+        }
+    }
+
+    saves
+        .0
+        .retain(|_, task| block_on(future::poll_once(task)).is_none());
+}
+
+/// Saves every modified chunk and waits for the writer to commit everything queued before the
+/// app closes. Anything else saved on exit must be queued before this runs.
+pub fn save_on_exit(
+    mut exit: EventReader<AppExit>,
+    mut level: ResMut<Level>,
+    config: Res<Config>,
+    registry: Res<SharedBlockRegistry>,
+    remote: Option<Res<RemoteLevel>>,
+    mut saves: ResMut<UnloadedSaves>,
+    mut save_tasks: Query<&mut SaveTask>,
+) {
+    if exit.is_empty() {
+        return;
+    }
+
+    exit.clear();
+
+    for mut save_task in save_tasks.iter_mut() {
+        block_on(&mut save_task.0);
+    }
+
+    for (_, save) in saves.0.drain() {
+        block_on(save);
+    }
+
+    if remote.is_none() {
+        let positions = level.loaded_chunks.keys().copied().collect_vec();
+
+        for pos in positions {
+            save_now(&mut level, pos, &registry, config.chunk_compression);
+        }
+    }
+
+    level.writer.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+
+    use bevy::{render::camera::CameraProjection, tasks::TaskPool};
+
+    use crate::{
+        edit::{BlocksEdited, EditHistory, WorldEdit},
+        level::SqliteStorage,
+        test_utils::{memory_level, test_registry},
+    };
+
+    use super::*;
+
+    fn open_level(path: &Path) -> Level {
+        Level::new(
+            Arc::new(SqliteStorage::open(path).unwrap()),
+            Perlin::default(),
         )
-        .unwrap();
     }
 
-    drop(conn);
-    build_chunk(adjacent, chunk, registry)
+    #[test]
+    fn test_chunk_offsets() {
+        let offsets = chunk_offsets(RenderDistance::new(4, 2));
+
+        assert_eq!(offsets[0], ChunkPos::default());
+        assert!(offsets.contains(&ChunkPos::new(4, 2, 0)));
+        assert!(!offsets.contains(&ChunkPos::new(0, 3, 0)));
+        assert!(!offsets.contains(&ChunkPos::new(3, 0, 3)));
+
+        let length = |pos: &ChunkPos| pos.x * pos.x + pos.y * pos.y + pos.z * pos.z;
+        assert!(offsets
+            .windows(2)
+            .all(|pair| length(&pair[0]) <= length(&pair[1])));
+    }
+
+    #[test]
+    fn test_chunk_priority() {
+        // Looking along +x from the middle of the origin chunk.
+        let origin = ChunkPos::default().center();
+        let transform = Transform::from_translation(origin).looking_to(Vec3::X, Vec3::Y);
+        let projection = PerspectiveProjection::default().get_projection_matrix();
+        let view = CameraView {
+            forward: Vec3::X,
+            frustum: Frustum::from_view_projection(
+                &(projection * transform.compute_matrix().inverse()),
+            ),
+        };
+
+        let ahead = ChunkPos::new(4, 0, 0);
+        let beside = ChunkPos::new(0, 0, 4);
+        let behind = ChunkPos::new(-4, 0, 0);
+        assert!(view.contains(ahead));
+        assert!(!view.contains(beside));
+        assert!(!view.contains(behind));
+
+        let priority = |pos| chunk_priority(pos, origin, Some(&view));
+        assert!(priority(ahead) < priority(ChunkPos::new(2, 0, 2)));
+        assert!(priority(ChunkPos::new(2, 0, 2)) < priority(beside));
+        assert_eq!(priority(beside), priority(behind));
+        assert!(priority(ChunkPos::new(-1, 0, 0)) < priority(ChunkPos::new(8, 0, 0)));
+        assert_eq!(chunk_priority(ahead, origin, None), priority(behind));
+    }
+
+    #[test]
+    fn test_lod_scale() {
+        let loaders = [ChunkPos::default().center()];
+
+        assert_eq!(lod_scale(ChunkPos::new(8, 0, 0), &loaders, 8), 1);
+        assert_eq!(lod_scale(ChunkPos::new(9, 0, 0), &loaders, 8), 2);
+        assert_eq!(lod_scale(ChunkPos::new(0, 0, 20), &loaders, 8), 4);
+        assert_eq!(lod_scale(ChunkPos::new(0, 0, 33), &loaders, 8), 8);
+        assert_eq!(lod_scale(ChunkPos::new(200, 0, 0), &loaders, 8), 8);
+        assert_eq!(lod_scale(ChunkPos::new(200, 0, 0), &[], 8), 1);
+    }
+
+    #[test]
+    fn test_only_modified_chunks_saved() {
+        AsyncComputeTaskPool::init(TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<SharedBlockRegistry>();
+        let registry = Arc::clone(world.resource::<SharedBlockRegistry>());
+        *registry.write().unwrap() = test_registry(&["dirt"]);
+        let dirt = registry.read().unwrap().block_id("dirt");
+
+        let edited = ChunkPos::new(0, 0, 0);
+        let neighbour = ChunkPos::new(1, 0, 0);
+        let generated = ChunkPos::new(0, 0, 4);
+
+        let mut level = memory_level();
+        let mut entities = ChunkEntities::default();
+
+        for pos in [edited, neighbour, generated] {
+            let chunk = generate_chunk(level.noise(), pos, Arc::clone(&registry));
+            level.add_chunk(pos, chunk);
+            entities.insert(pos, world.spawn(pos).id());
+        }
+
+        world.insert_resource(level);
+        world.insert_resource(entities);
+        world.insert_resource(Config::default());
+        world.init_resource::<UnloadedSaves>();
+        world.init_resource::<EditHistory>();
+        world.init_resource::<Events<BlocksEdited>>();
+
+        // On the edge of its chunk, so the neighbour is re-meshed too.
+        let pos = BlockPos::new(CHUNK_SIZE as i32 - 1, 30, 5);
+        Schedule::default()
+            .add_systems(move |mut edit: WorldEdit| edit.set_block(pos, Some(dirt)))
+            .run(&mut world);
+
+        let neighbour_entity = world.resource::<ChunkEntities>().get(neighbour).unwrap();
+        assert!(world.get::<Dirty>(neighbour_entity).is_some());
+
+        let mut save = Schedule::default();
+        save.add_systems((save_chunks, apply_deferred).chain());
+        save.run(&mut world);
+
+        let saving = world
+            .query_filtered::<&ChunkPos, With<SaveTask>>()
+            .iter(&world)
+            .copied()
+            .collect_vec();
+        assert_eq!(saving, [edited]);
+        assert!(!world
+            .resource::<Level>()
+            .chunk(edited)
+            .unwrap()
+            .is_modified());
+
+        let finish = |mut commands: Commands, mut tasks: Query<(Entity, &mut SaveTask)>| {
+            for (entity, mut task) in tasks.iter_mut() {
+                block_on(&mut task.0);
+                commands.entity(entity).remove::<SaveTask>();
+            }
+        };
+        Schedule::default().add_systems(finish).run(&mut world);
+
+        // Saved once, so it isn't queued again.
+        save.run(&mut world);
+        assert!(world
+            .query_filtered::<(), With<SaveTask>>()
+            .iter(&world)
+            .next()
+            .is_none());
+
+        let level = world.resource::<Level>();
+        level.writer.flush();
+        assert!(level.storage.load_chunk(edited).unwrap().is_some());
+        assert!(level.storage.load_chunk(neighbour).unwrap().is_none());
+        assert!(level.storage.load_chunk(generated).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_edits_survive_restart() {
+        AsyncComputeTaskPool::init(TaskPool::default);
+
+        let path = env::temp_dir().join(format!("restart-{}.sqlite", std::process::id()));
+        let mut world = World::new();
+        world.init_resource::<SharedBlockRegistry>();
+        let registry = Arc::clone(world.resource::<SharedBlockRegistry>());
+        *registry.write().unwrap() = test_registry(&["dirt"]);
+        let dirt = registry.read().unwrap().block_id("dirt");
+
+        // High enough that generated terrain is empty, so only saved edits can fill it.
+        let unloaded = ChunkPos::new(100, 2, 0);
+        let kept = ChunkPos::new(0, 2, 0);
+
+        let mut level = open_level(&path);
+        let mut entities = ChunkEntities::default();
+
+        for pos in [unloaded, kept] {
+            let mut chunk = Chunk::default();
+            *chunk.block_mut(1, 2, 3) = Some(dirt);
+            chunk.set_modified(true);
+            level.add_chunk(pos, chunk);
+            let entity = world.spawn(pos).id();
+            entities.insert(pos, entity);
+        }
+
+        world.insert_resource(level);
+        world.insert_resource(entities);
+        world.init_resource::<ChunkCache>();
+        world.init_resource::<UnloadedSaves>();
+        world.init_resource::<TaskStats>();
+        world.insert_resource(Config::default());
+        world.init_resource::<Events<AppExit>>();
+        world.spawn((Transform::default(), ChunkLoader));
+
+        Schedule::default()
+            .add_systems(remove_chunks)
+            .run(&mut world);
+        assert!(world.resource::<Level>().chunk(unloaded).is_none());
+        assert!(world.resource::<Level>().chunk(kept).is_some());
+        assert!(!world.resource::<ChunkEntities>().contains(unloaded));
+        assert_eq!(world.resource::<ChunkCache>().len(), 1);
+        // Saved on the thread pool, which exiting waits for.
+        assert!(world.resource::<UnloadedSaves>().0.contains_key(&unloaded));
+
+        world.send_event(AppExit);
+        Schedule::default()
+            .add_systems(save_on_exit)
+            .run(&mut world);
+        drop(world);
+
+        let level = open_level(&path);
+
+        for pos in [unloaded, kept] {
+            let chunk = block_on(load_chunk(
+                pos,
+                level.noise(),
+                Arc::clone(&registry),
+                Arc::clone(&level.storage),
+                level.writer.clone(),
+            ));
+            assert_eq!(*chunk.block(1, 2, 3), Some(dirt));
+        }
+
+        drop(level);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.as_os_str().to_owned();
+            file.push(suffix);
+            fs::remove_file(file).ok();
+        }
+    }
 }
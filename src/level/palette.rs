@@ -0,0 +1,173 @@
+use std::io;
+
+use indexmap::IndexSet;
+
+/// Encodes a run of blocks as a palette of names followed by run-length encoded palette
+/// indices, where index 0 is air.
+pub fn encode_palette<'a>(blocks: impl IntoIterator<Item = Option<&'a str>>) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut names = IndexSet::new();
+    let mut last = None;
+    let mut count = 0;
+
+    for block in blocks {
+        let index = block
+            .map(|name| names.insert_full(name).0 + 1)
+            .unwrap_or_default();
+
+        if last == Some(index) && count < u16::MAX as usize {
+            count += 1;
+        } else {
+            if let Some(index) = last {
+                data.extend((count as u16).to_be_bytes());
+                data.extend((index as u16).to_be_bytes());
+            }
+            last = Some(index);
+            count = 1;
+        }
+    }
+
+    if let Some(index) = last {
+        data.extend((count as u16).to_be_bytes());
+        data.extend((index as u16).to_be_bytes());
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend((names.len() as u16).to_be_bytes());
+
+    for name in names {
+        bytes.push(name.len().try_into().unwrap());
+        bytes.extend(name.as_bytes());
+    }
+
+    bytes.extend(data);
+    bytes
+}
+
+/// Decodes the output of [`encode_palette`] into the palette names and one palette index per
+/// block, where index 0 is air and index `n` refers to `names[n - 1]`. Fails unless the runs
+/// cover exactly `len` blocks with nothing after them, so corrupt data can't expand past the
+/// size the caller expects.
+pub fn decode_palette(bytes: &[u8], len: usize) -> io::Result<(Vec<String>, Vec<u16>)> {
+    let mut i = 0;
+
+    let mut read = |len: usize| {
+        let slice = bytes.get(i..i + len).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "truncated palette data")
+        })?;
+        i += len;
+        Ok::<_, io::Error>(slice)
+    };
+
+    let name_list_len = u16::from_be_bytes(read(2)?.try_into().unwrap()) as usize;
+    let mut names = Vec::with_capacity(name_list_len);
+
+    for _ in 0..name_list_len {
+        let name_len = read(1)?[0] as usize;
+        names.push(String::from_utf8_lossy(read(name_len)?).into_owned());
+    }
+
+    let mut indices = Vec::with_capacity(len);
+
+    while indices.len() < len {
+        let run = read(4)?;
+        let count = u16::from_be_bytes([run[0], run[1]]) as usize;
+        let index = u16::from_be_bytes([run[2], run[3]]);
+
+        if index as usize > names.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "palette index out of range",
+            ));
+        }
+
+        if indices.len() + count > len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "palette data covers too many blocks",
+            ));
+        }
+
+        indices.extend(std::iter::repeat_n(index, count));
+    }
+
+    if i != bytes.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "trailing bytes after palette data",
+        ));
+    }
+
+    Ok((names, indices))
+}
+
+/// Re-encodes palette data with every name passed through `map`, where `None` turns the block
+/// into air. Blocks that end up with the same name share a palette entry.
+pub fn remap_palette(
+    bytes: &[u8],
+    len: usize,
+    map: impl Fn(&str) -> Option<String>,
+) -> io::Result<Vec<u8>> {
+    let (names, indices) = decode_palette(bytes, len)?;
+    let names = names.iter().map(|name| map(name)).collect::<Vec<_>>();
+
+    Ok(encode_palette(indices.into_iter().map(|index| {
+        index
+            .checked_sub(1)
+            .and_then(|index| names[index as usize].as_deref())
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_round_trip() {
+        let blocks = [
+            None,
+            Some("dirt"),
+            Some("dirt"),
+            Some("stone"),
+            None,
+            Some("dirt"),
+        ];
+        let bytes = encode_palette(blocks);
+        let (names, indices) = decode_palette(&bytes, blocks.len()).unwrap();
+
+        assert_eq!(names, ["dirt", "stone"]);
+        assert_eq!(indices, [0, 1, 1, 2, 0, 1]);
+
+        assert!(decode_palette(&bytes, blocks.len() - 1).is_err());
+        assert!(decode_palette(&bytes, blocks.len() + 1).is_err());
+        assert!(decode_palette(&bytes[..bytes.len() - 1], blocks.len()).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.extend([0, 0, 0, 0]);
+        assert!(decode_palette(&trailing, blocks.len()).is_err());
+    }
+
+    #[test]
+    fn test_remap() {
+        let bytes = encode_palette([Some("dirt"), Some("grass"), Some("stone"), None]);
+        let bytes = remap_palette(&bytes, 4, |name| match name {
+            "grass" => Some("dirt".to_string()),
+            "stone" => None,
+            name => Some(name.to_string()),
+        })
+        .unwrap();
+        let (names, indices) = decode_palette(&bytes, 4).unwrap();
+
+        assert_eq!(names, ["dirt"]);
+        assert_eq!(indices, [1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_long_runs() {
+        let len = u16::MAX as usize * 2 + 5;
+        let bytes = encode_palette(std::iter::repeat_n(Some("dirt"), len));
+        let (_, indices) = decode_palette(&bytes, len).unwrap();
+
+        assert_eq!(indices.len(), len);
+    }
+}
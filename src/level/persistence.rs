@@ -0,0 +1,309 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use bevy::{log::error, utils::HashMap};
+use itertools::Itertools;
+
+use crate::position::ChunkPos;
+
+use super::{ChunkStorage, StorageWrite};
+
+/// The most writes saved in one batch.
+const MAX_BATCH: usize = 256;
+
+/// How long a failed batch waits to be retried when nothing else is queued.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+enum Request {
+    Write(StorageWrite, u64),
+    Flush(Sender<()>),
+}
+
+/// Writes queued by [`WorldWriter::write`] that haven't been committed yet, so loads see them.
+#[derive(Default)]
+struct PendingWrites {
+    next_generation: u64,
+    chunks: HashMap<ChunkPos, (u64, Arc<Vec<u8>>)>,
+}
+
+/// Saves chunks and world metadata on a dedicated thread, batching queued writes so that
+/// neither the main thread nor save tasks wait on the disk.
+#[derive(Clone)]
+pub struct WorldWriter {
+    sender: Sender<Request>,
+    pending: Arc<Mutex<PendingWrites>>,
+}
+
+impl WorldWriter {
+    pub fn spawn(storage: Arc<dyn ChunkStorage>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let pending = Arc::new(Mutex::new(PendingWrites::default()));
+
+        let thread_pending = Arc::clone(&pending);
+        thread::Builder::new()
+            .name("world writer".to_string())
+            .spawn(move || run_writer(storage, receiver, thread_pending))
+            .unwrap();
+
+        Self { sender, pending }
+    }
+
+    /// Queues a chunk blob to be written, replacing any queued write for the same position.
+    pub fn write(&self, pos: ChunkPos, data: Vec<u8>) {
+        let data = Arc::new(data);
+        let mut pending = self.pending.lock().unwrap();
+        let generation = pending.next_generation;
+        pending.next_generation += 1;
+        pending.chunks.insert(pos, (generation, Arc::clone(&data)));
+
+        self.send(Request::Write(
+            StorageWrite::Chunk { pos, data },
+            generation,
+        ));
+    }
+
+    /// Queues a world-wide value to be written. Writes are committed in the order they're queued.
+    pub fn write_metadata(&self, key: &str, value: Vec<u8>) {
+        let write = StorageWrite::Metadata {
+            key: key.to_string(),
+            value,
+        };
+        self.send(Request::Write(write, 0));
+    }
+
+    /// The newest blob queued for `pos` that hasn't been committed yet.
+    pub fn pending(&self, pos: ChunkPos) -> Option<Vec<u8>> {
+        let pending = self.pending.lock().unwrap();
+        pending.chunks.get(&pos).map(|entry| entry.1.to_vec())
+    }
+
+    /// Blocks until every write queued so far has been attempted. Writes that failed are
+    /// logged and still retried afterwards.
+    pub fn flush(&self) {
+        let (sender, receiver) = mpsc::channel();
+
+        if self.sender.send(Request::Flush(sender)).is_ok() {
+            // Fails only if the writer has died, in which case there is nothing to wait for.
+            receiver.recv().ok();
+        }
+    }
+
+    fn send(&self, request: Request) {
+        if self.sender.send(request).is_err() {
+            error!("The world writer has stopped, so the world can no longer be saved");
+        }
+    }
+}
+
+fn run_writer(
+    storage: Arc<dyn ChunkStorage>,
+    receiver: Receiver<Request>,
+    pending: Arc<Mutex<PendingWrites>>,
+) {
+    // Writes from a batch that failed, with their generations, retried before anything newer.
+    let mut failed = Vec::new();
+
+    loop {
+        let request = if failed.is_empty() {
+            let Ok(request) = receiver.recv() else {
+                break;
+            };
+            Some(request)
+        } else {
+            match receiver.recv_timeout(RETRY_DELAY) {
+                Ok(request) => Some(request),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        };
+
+        let mut writes = std::mem::take(&mut failed);
+        let mut flushes = Vec::new();
+
+        for request in request.into_iter().chain(receiver.try_iter()) {
+            match request {
+                Request::Write(write, generation) => {
+                    // Only the newest value for a key is worth saving, so repeated writes to a
+                    // key don't pile up while saves keep failing.
+                    if let StorageWrite::Metadata { key, .. } = &write {
+                        writes.retain(|(queued, _)| match queued {
+                            StorageWrite::Metadata { key: other, .. } => other != key,
+                            StorageWrite::Chunk { .. } => true,
+                        });
+                    }
+
+                    writes.push((write, generation));
+                }
+                Request::Flush(sender) => flushes.push(sender),
+            }
+        }
+
+        // Batches are committed in order, so a newer write for a chunk still lands last.
+        for batch in &writes.into_iter().chunks(MAX_BATCH) {
+            let (batch, generations): (Vec<_>, Vec<_>) = batch.unzip();
+            let result = storage.save(&batch);
+            let mut pending = pending.lock().unwrap();
+
+            // A chunk write is out of date once a newer write for the same position is queued.
+            let current = |write: &StorageWrite, generation: u64| match write {
+                StorageWrite::Chunk { pos, .. } => pending
+                    .chunks
+                    .get(pos)
+                    .is_some_and(|entry| entry.0 == generation),
+                StorageWrite::Metadata { .. } => true,
+            };
+
+            match result {
+                Ok(()) => {
+                    let committed = batch
+                        .iter()
+                        .zip(generations)
+                        .filter_map(|(write, generation)| match write {
+                            StorageWrite::Chunk { pos, .. } if current(write, generation) => {
+                                Some(*pos)
+                            }
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+
+                    for pos in committed {
+                        pending.chunks.remove(&pos);
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to save the world, retrying: {err}");
+                    failed.extend(
+                        batch
+                            .into_iter()
+                            .zip(generations)
+                            .filter(|(write, generation)| current(write, *generation)),
+                    );
+                }
+            }
+        }
+
+        for sender in flushes {
+            sender.send(()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use crate::level::MemoryStorage;
+
+    use super::*;
+
+    /// Fails to save while `failing` is set, like a full disk.
+    #[derive(Default)]
+    struct FailingStorage {
+        storage: MemoryStorage,
+        failing: AtomicBool,
+        largest_batch: AtomicUsize,
+    }
+
+    impl ChunkStorage for FailingStorage {
+        fn load_chunk(&self, pos: ChunkPos) -> io::Result<Option<Vec<u8>>> {
+            self.storage.load_chunk(pos)
+        }
+
+        fn load_metadata(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+            self.storage.load_metadata(key)
+        }
+
+        fn save(&self, batch: &[StorageWrite]) -> io::Result<()> {
+            self.largest_batch.fetch_max(batch.len(), Ordering::SeqCst);
+
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+            }
+
+            self.storage.save(batch)
+        }
+
+        fn chunk_positions(&self) -> io::Result<Vec<ChunkPos>> {
+            self.storage.chunk_positions()
+        }
+
+        fn delete_chunks(&self, positions: &[ChunkPos]) -> io::Result<()> {
+            self.storage.delete_chunks(positions)
+        }
+    }
+
+    #[test]
+    fn test_writer() {
+        let storage = Arc::new(MemoryStorage::default());
+        let writer = WorldWriter::spawn(storage.clone());
+        let pos = ChunkPos::new(0, -1, 2);
+
+        writer.write(pos, vec![1]);
+        writer.write(pos, vec![2]);
+        writer.write_metadata("seed", vec![3]);
+        writer.flush();
+        assert_eq!(writer.pending(pos), None);
+
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(vec![2]));
+        assert_eq!(storage.load_metadata("seed").unwrap(), Some(vec![3]));
+    }
+
+    #[test]
+    fn test_writer_retries() {
+        let storage = Arc::new(FailingStorage::default());
+        storage.failing.store(true, Ordering::SeqCst);
+        let writer = WorldWriter::spawn(storage.clone());
+        let pos = ChunkPos::new(3, 0, -1);
+
+        // Failed writes stay pending, so loads still see them.
+        writer.write(pos, vec![1]);
+        writer.write_metadata("seed", vec![2]);
+        writer.flush();
+        assert_eq!(writer.pending(pos), Some(vec![1]));
+        writer.write(pos, vec![3]);
+        writer.flush();
+        assert_eq!(writer.pending(pos), Some(vec![3]));
+
+        storage.failing.store(false, Ordering::SeqCst);
+        writer.flush();
+        assert_eq!(writer.pending(pos), None);
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(vec![3]));
+        assert_eq!(storage.load_metadata("seed").unwrap(), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_writer_retry_backlog() {
+        let storage = Arc::new(FailingStorage::default());
+        storage.failing.store(true, Ordering::SeqCst);
+        let writer = WorldWriter::spawn(storage.clone());
+
+        // Only the newest value for each key is kept, and retries are still split into batches.
+        for i in 0..MAX_BATCH {
+            writer.write(ChunkPos::new(i as i32, 0, 0), vec![i as u8]);
+            writer.write_metadata("player", vec![i as u8]);
+            writer.flush();
+        }
+
+        storage.failing.store(false, Ordering::SeqCst);
+        writer.flush();
+        assert!(storage.largest_batch.load(Ordering::SeqCst) <= MAX_BATCH);
+
+        let last = MAX_BATCH - 1;
+        let pos = ChunkPos::new(last as i32, 0, 0);
+        assert_eq!(writer.pending(pos), None);
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(vec![last as u8]));
+        assert_eq!(
+            storage.load_metadata("player").unwrap(),
+            Some(vec![last as u8])
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::{io, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::position::ChunkPos;
+
+mod memory;
+mod region;
+mod sqlite;
+
+pub use memory::*;
+pub use region::*;
+pub use sqlite::*;
+
+/// Which [`ChunkStorage`] a world is saved with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StorageBackend {
+    #[default]
+    Sqlite,
+    Region,
+}
+
+pub enum StorageWrite {
+    Chunk { pos: ChunkPos, data: Arc<Vec<u8>> },
+    Metadata { key: String, value: Vec<u8> },
+}
+
+/// Where chunk blobs and world-wide values are kept. Implementations synchronise internally, so
+/// chunks can be loaded from task threads while the [`WorldWriter`](super::WorldWriter) saves.
+pub trait ChunkStorage: Send + Sync {
+    fn load_chunk(&self, pos: ChunkPos) -> io::Result<Option<Vec<u8>>>;
+
+    fn load_metadata(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Applies the writes in order, all or nothing if the backend supports it.
+    fn save(&self, batch: &[StorageWrite]) -> io::Result<()>;
+
+    fn save_chunk(&self, pos: ChunkPos, data: &[u8]) -> io::Result<()> {
+        self.save(&[StorageWrite::Chunk {
+            pos,
+            data: Arc::new(data.to_vec()),
+        }])
+    }
+
+    fn save_metadata(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        self.save(&[StorageWrite::Metadata {
+            key: key.to_string(),
+            value: value.to_vec(),
+        }])
+    }
+
+    /// Every chunk that has been saved, in no particular order.
+    fn chunk_positions(&self) -> io::Result<Vec<ChunkPos>>;
+
+    /// Forgets saved chunks, so they are generated again the next time they load.
+    fn delete_chunks(&self, positions: &[ChunkPos]) -> io::Result<()>;
+
+    /// Reclaims the space left by rewritten and deleted chunks.
+    fn compact(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+    };
+
+    use rusqlite::Connection;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        remove_path(&path);
+        path
+    }
+
+    fn remove_path(path: &Path) {
+        fs::remove_dir_all(path).ok();
+
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.as_os_str().to_owned();
+            file.push(suffix);
+            fs::remove_file(file).ok();
+        }
+    }
+
+    /// The behaviour every backend has to share.
+    fn check_storage(storage: &dyn ChunkStorage) {
+        let pos = ChunkPos::new(1, -2, 3);
+        assert_eq!(storage.load_chunk(pos).unwrap(), None);
+        assert_eq!(storage.load_metadata("seed").unwrap(), None);
+
+        storage.save_chunk(pos, &[1, 2, 3]).unwrap();
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(storage.load_chunk(pos + ChunkPos::X).unwrap(), None);
+
+        // Growing a chunk past the space it had, then shrinking it again.
+        let large = (0..10_000).map(|i| i as u8).collect::<Vec<_>>();
+        storage.save_chunk(pos, &large).unwrap();
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(large));
+        storage.save_chunk(pos, &[4]).unwrap();
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(vec![4]));
+
+        // Neighbours on both sides of the origin, which share regions with each other.
+        let batch = (-10..10)
+            .map(|x| StorageWrite::Chunk {
+                pos: ChunkPos::new(x, x, -x),
+                data: Arc::new(vec![x as u8; 5000]),
+            })
+            .collect::<Vec<_>>();
+        storage.save(&batch).unwrap();
+        for x in -10..10 {
+            let data = storage.load_chunk(ChunkPos::new(x, x, -x)).unwrap();
+            assert_eq!(data, Some(vec![x as u8; 5000]));
+        }
+
+        let mut positions = storage.chunk_positions().unwrap();
+        positions.sort_by_key(|pos| (pos.x, pos.y, pos.z));
+        let mut expected = (-10..10)
+            .map(|x| ChunkPos::new(x, x, -x))
+            .chain([pos])
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|pos| (pos.x, pos.y, pos.z));
+        assert_eq!(positions, expected);
+
+        let deleted = ChunkPos::new(-3, -3, 3);
+        storage
+            .delete_chunks(&[deleted, pos + ChunkPos::X])
+            .unwrap();
+        assert_eq!(storage.load_chunk(deleted).unwrap(), None);
+        assert_eq!(storage.chunk_positions().unwrap().len(), expected.len() - 1);
+
+        storage.compact().unwrap();
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(vec![4]));
+        for x in (-10..10).filter(|&x| x != -3) {
+            let data = storage.load_chunk(ChunkPos::new(x, x, -x)).unwrap();
+            assert_eq!(data, Some(vec![x as u8; 5000]));
+        }
+
+        storage.save_metadata("seed", &[5]).unwrap();
+        storage.save_metadata("seed", &[6]).unwrap();
+        assert_eq!(storage.load_metadata("seed").unwrap(), Some(vec![6]));
+    }
+
+    /// Saves with one instance and loads with another, as across a restart.
+    fn check_reopen(open: impl Fn() -> Box<dyn ChunkStorage>) {
+        let pos = ChunkPos::new(-7, 0, 9);
+        let storage = open();
+        storage.save_chunk(pos, &[7; 100]).unwrap();
+        storage.save_metadata("player", &[8]).unwrap();
+        drop(storage);
+
+        let storage = open();
+        assert_eq!(storage.load_chunk(pos).unwrap(), Some(vec![7; 100]));
+        assert_eq!(storage.load_metadata("player").unwrap(), Some(vec![8]));
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        check_storage(&MemoryStorage::default());
+    }
+
+    #[test]
+    fn test_sqlite_storage() {
+        let path = temp_path("storage.sqlite");
+        check_storage(&SqliteStorage::open(&path).unwrap());
+        remove_path(&path);
+        check_reopen(|| Box::new(SqliteStorage::open(&path).unwrap()));
+        remove_path(&path);
+    }
+
+    #[test]
+    fn test_region_storage() {
+        let path = temp_path("regions");
+        check_storage(&RegionStorage::open(&path).unwrap());
+        remove_path(&path);
+        check_reopen(|| Box::new(RegionStorage::open(&path).unwrap()));
+        remove_path(&path);
+
+        // Rewrites go to unused space, so the old copy survives an interrupted save, and
+        // alternate between two places rather than growing the file.
+        let storage = RegionStorage::open(&path).unwrap();
+        for i in 0..10 {
+            storage
+                .save_chunk(ChunkPos::new(1, 2, 3), &[i; 100])
+                .unwrap();
+        }
+        let region = fs::metadata(path.join("r.0.0.0.region")).unwrap();
+        assert_eq!(region.len(), 3 * 4096);
+        remove_path(&path);
+    }
+
+    #[test]
+    fn test_migrate_legacy() {
+        let path = temp_path("migrate.sqlite");
+        let legacy = Connection::open(&path).unwrap();
+        legacy
+            .execute_batch(
+                "CREATE TABLE `chunks` (`x` INTEGER, `y` INTEGER, `z` INTEGER, `data` BLOB);
+                INSERT INTO `chunks` VALUES (1, 2, 3, x'01');
+                INSERT INTO `chunks` VALUES (1, 2, 3, x'02');",
+            )
+            .unwrap();
+        drop(legacy);
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(
+            storage.load_chunk(ChunkPos::new(1, 2, 3)).unwrap(),
+            Some(vec![2])
+        );
+
+        drop(storage);
+        remove_path(&path);
+    }
+
+    #[test]
+    fn test_migrate_interrupted() {
+        // A version 1 database whose upgrade to version 2 made its table but never recorded it.
+        let path = temp_path("interrupted.sqlite");
+        let old = Connection::open(&path).unwrap();
+        old.execute_batch(
+            "CREATE TABLE `chunks` (
+                `x` INTEGER NOT NULL,
+                `y` INTEGER NOT NULL,
+                `z` INTEGER NOT NULL,
+                `data` BLOB NOT NULL,
+                PRIMARY KEY (`x`, `y`, `z`)
+            ) WITHOUT ROWID;
+            CREATE TABLE `metadata` (`key` TEXT NOT NULL PRIMARY KEY, `value` BLOB NOT NULL);
+            PRAGMA user_version = 1;",
+        )
+        .unwrap();
+        drop(old);
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        storage.save_metadata("seed", &[1]).unwrap();
+        drop(storage);
+
+        let version: i32 = Connection::open(&path)
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, 2);
+        remove_path(&path);
+    }
+}
@@ -0,0 +1,56 @@
+use std::{io, sync::Mutex};
+
+use bevy::utils::HashMap;
+
+use crate::position::ChunkPos;
+
+use super::{ChunkStorage, StorageWrite};
+
+/// Keeps everything in memory and loses it when dropped, for tests and throwaway worlds.
+#[derive(Default)]
+pub struct MemoryStorage {
+    chunks: Mutex<HashMap<ChunkPos, Vec<u8>>>,
+    metadata: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl ChunkStorage for MemoryStorage {
+    fn load_chunk(&self, pos: ChunkPos) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.chunks.lock().unwrap().get(&pos).cloned())
+    }
+
+    fn load_metadata(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.metadata.lock().unwrap().get(key).cloned())
+    }
+
+    fn save(&self, batch: &[StorageWrite]) -> io::Result<()> {
+        let mut chunks = self.chunks.lock().unwrap();
+        let mut metadata = self.metadata.lock().unwrap();
+
+        for write in batch {
+            match write {
+                StorageWrite::Chunk { pos, data } => {
+                    chunks.insert(*pos, data.to_vec());
+                }
+                StorageWrite::Metadata { key, value } => {
+                    metadata.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn chunk_positions(&self) -> io::Result<Vec<ChunkPos>> {
+        Ok(self.chunks.lock().unwrap().keys().copied().collect())
+    }
+
+    fn delete_chunks(&self, positions: &[ChunkPos]) -> io::Result<()> {
+        let mut chunks = self.chunks.lock().unwrap();
+
+        for pos in positions {
+            chunks.remove(pos);
+        }
+
+        Ok(())
+    }
+}
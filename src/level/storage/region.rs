@@ -0,0 +1,332 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use bevy::utils::HashMap;
+
+use crate::position::ChunkPos;
+
+use super::{ChunkStorage, StorageWrite};
+
+/// Chunks along each side of a region.
+const REGION_SIZE: i32 = 8;
+
+const REGION_VOLUME: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+/// Chunks are stored in whole sectors, so the space one leaves behind fits most others.
+const SECTOR_SIZE: u64 = 4096;
+
+/// An offset table with a first sector and byte length for every chunk in the region.
+const HEADER_SECTORS: u32 = (REGION_VOLUME as u64 * 8).div_ceil(SECTOR_SIZE) as u32;
+
+/// Groups the chunks of each 8³ region into one file, found through an offset table at its
+/// start. Writes aren't atomic, but a chunk's data is always written to unused sectors and synced
+/// before the table points at it, so an interrupted save leaves the previous version in place.
+pub struct RegionStorage {
+    directory: PathBuf,
+    regions: Mutex<HashMap<ChunkPos, Region>>,
+}
+
+impl RegionStorage {
+    pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(directory.join("metadata"))?;
+
+        Ok(Self {
+            directory,
+            regions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn region_path(&self, region_pos: ChunkPos) -> PathBuf {
+        let ChunkPos { x, y, z } = region_pos;
+        self.directory.join(format!("r.{x}.{y}.{z}.region"))
+    }
+
+    /// The regions that have files, parsed from their names.
+    fn region_positions(&self) -> io::Result<Vec<ChunkPos>> {
+        let mut positions = Vec::new();
+
+        for entry in fs::read_dir(&self.directory)? {
+            let name = entry?.file_name();
+            let Some(coordinates) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("r."))
+                .and_then(|name| name.strip_suffix(".region"))
+            else {
+                continue;
+            };
+
+            if let [Ok(x), Ok(y), Ok(z)] =
+                coordinates.split('.').map(str::parse).collect::<Vec<_>>()[..]
+            {
+                positions.push(ChunkPos::new(x, y, z));
+            }
+        }
+
+        Ok(positions)
+    }
+
+    fn metadata_path(&self, key: &str) -> PathBuf {
+        self.directory.join("metadata").join(key)
+    }
+
+    /// Runs `f` on the region containing `pos`, or returns `None` if there is no such region
+    /// and `create` is false.
+    fn with_region<T>(
+        &self,
+        pos: ChunkPos,
+        create: bool,
+        f: impl FnOnce(&mut Region, usize) -> io::Result<T>,
+    ) -> io::Result<Option<T>> {
+        let (region_pos, index) = region_index(pos);
+        let mut regions = self.regions.lock().unwrap();
+
+        if !regions.contains_key(&region_pos) {
+            let path = self.region_path(region_pos);
+
+            if !create && !path.exists() {
+                return Ok(None);
+            }
+
+            regions.insert(region_pos, Region::open(&path)?);
+        }
+
+        f(regions.get_mut(&region_pos).unwrap(), index).map(Some)
+    }
+}
+
+impl ChunkStorage for RegionStorage {
+    fn load_chunk(&self, pos: ChunkPos) -> io::Result<Option<Vec<u8>>> {
+        Ok(self
+            .with_region(pos, false, |region, index| region.read(index))?
+            .flatten())
+    }
+
+    fn load_metadata(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.metadata_path(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&self, batch: &[StorageWrite]) -> io::Result<()> {
+        for write in batch {
+            match write {
+                StorageWrite::Chunk { pos, data } => {
+                    self.with_region(*pos, true, |region, index| region.write(index, data))?;
+                }
+                StorageWrite::Metadata { key, value } => {
+                    // Replaced by renaming, so a reader never sees half a value.
+                    let path = self.metadata_path(key);
+                    let temp = path.with_extension("tmp");
+                    fs::write(&temp, value)?;
+                    fs::rename(temp, path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn chunk_positions(&self) -> io::Result<Vec<ChunkPos>> {
+        let mut positions = Vec::new();
+
+        for region_pos in self.region_positions()? {
+            let origin = region_pos * REGION_SIZE;
+            self.with_region(origin, false, |region, _| {
+                for (index, &(_, len)) in region.entries.iter().enumerate() {
+                    if len > 0 {
+                        positions.push(origin + region_offset(index));
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(positions)
+    }
+
+    fn delete_chunks(&self, positions: &[ChunkPos]) -> io::Result<()> {
+        for &pos in positions {
+            self.with_region(pos, false, |region, index| region.delete(index))?;
+        }
+
+        Ok(())
+    }
+
+    fn compact(&self) -> io::Result<()> {
+        let mut regions = self.regions.lock().unwrap();
+        regions.clear();
+
+        for region_pos in self.region_positions()? {
+            let path = self.region_path(region_pos);
+            let temp = path.with_extension("tmp");
+            fs::remove_file(&temp).ok();
+
+            let mut region = Region::open(&path)?;
+            let mut compacted = Region::open(&temp)?;
+
+            for index in 0..REGION_VOLUME {
+                if let Some(data) = region.read(index)? {
+                    compacted.write(index, &data)?;
+                }
+            }
+
+            drop((region, compacted));
+            fs::rename(temp, path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The region containing `pos` and the index of `pos` within it.
+fn region_index(pos: ChunkPos) -> (ChunkPos, usize) {
+    let region_pos = ChunkPos::new(
+        pos.x.div_euclid(REGION_SIZE),
+        pos.y.div_euclid(REGION_SIZE),
+        pos.z.div_euclid(REGION_SIZE),
+    );
+    let (x, y, z) = (
+        pos.x.rem_euclid(REGION_SIZE),
+        pos.y.rem_euclid(REGION_SIZE),
+        pos.z.rem_euclid(REGION_SIZE),
+    );
+
+    (
+        region_pos,
+        ((x * REGION_SIZE + y) * REGION_SIZE + z) as usize,
+    )
+}
+
+/// The inverse of the index returned by [`region_index`].
+fn region_offset(index: usize) -> ChunkPos {
+    let index = index as i32;
+    ChunkPos::new(
+        index / (REGION_SIZE * REGION_SIZE),
+        index / REGION_SIZE % REGION_SIZE,
+        index % REGION_SIZE,
+    )
+}
+
+fn sectors(len: u32) -> u32 {
+    (len as u64).div_ceil(SECTOR_SIZE) as u32
+}
+
+struct Region {
+    file: File,
+    /// The first sector and byte length of each chunk, with a length of zero if it is absent.
+    entries: Vec<(u32, u32)>,
+    /// The sector after the end of the file, where chunks go when no unused space fits them.
+    end: u32,
+}
+
+impl Region {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut header = vec![0; HEADER_SECTORS as usize * SECTOR_SIZE as usize];
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(&header)?;
+        } else {
+            file.read_exact(&mut header)?;
+        }
+
+        let entries = header
+            .chunks_exact(8)
+            .take(REGION_VOLUME)
+            .map(|entry| {
+                let sector = u32::from_be_bytes(entry[..4].try_into().unwrap());
+                let len = u32::from_be_bytes(entry[4..].try_into().unwrap());
+                (sector, len)
+            })
+            .collect();
+        let end = (file.metadata()?.len().div_ceil(SECTOR_SIZE) as u32).max(HEADER_SECTORS);
+
+        Ok(Self { file, entries, end })
+    }
+
+    fn read(&mut self, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let (sector, len) = self.entries[index];
+
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let mut data = vec![0; len as usize];
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    fn write(&mut self, index: usize, data: &[u8]) -> io::Result<()> {
+        let len = data.len() as u32;
+
+        let sector = self.unused_sectors(sectors(len));
+        self.end = self.end.max(sector + sectors(len));
+
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.write_all(data)?;
+        self.file.set_len(self.end as u64 * SECTOR_SIZE)?;
+
+        // The data must reach the disk before the header points at it, or a crash could leave the
+        // header pointing at sectors that were never written.
+        self.file.sync_data()?;
+
+        self.write_entry(index, (sector, len))
+    }
+
+    /// The first run of `count` sectors that no chunk uses, extending the file if none is long
+    /// enough. A chunk being rewritten still uses its old sectors, so they are never chosen.
+    fn unused_sectors(&self, count: u32) -> u32 {
+        let mut used = vec![false; self.end as usize];
+        used[..HEADER_SECTORS as usize].fill(true);
+
+        for &(sector, len) in &self.entries {
+            if len > 0 {
+                let start = (sector as usize).min(used.len());
+                let end = (sector as usize + sectors(len) as usize).min(used.len());
+                used[start..end].fill(true);
+            }
+        }
+
+        let mut run = 0;
+
+        for (sector, &used) in used.iter().enumerate() {
+            run = if used { 0 } else { run + 1 };
+
+            if run == count {
+                return sector as u32 + 1 - count;
+            }
+        }
+
+        // Unused sectors at the end of the file are extended rather than skipped.
+        self.end - run
+    }
+
+    fn delete(&mut self, index: usize) -> io::Result<()> {
+        self.write_entry(index, (0, 0))
+    }
+
+    fn write_entry(&mut self, index: usize, entry: (u32, u32)) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(index as u64 * 8))?;
+        self.file.write_all(&entry.0.to_be_bytes())?;
+        self.file.write_all(&entry.1.to_be_bytes())?;
+        self.entries[index] = entry;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use std::{io, path::Path, sync::Mutex};
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::position::ChunkPos;
+
+use super::{ChunkStorage, StorageWrite};
+
+/// Keeps every chunk as a row of one SQLite database.
+pub struct SqliteStorage {
+    /// Separate connections let loads read the last commit while a save is in progress.
+    reader: Mutex<Connection>,
+    writer: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            reader: Mutex::new(open_database(&path)?),
+            writer: Mutex::new(open_database(&path)?),
+        })
+    }
+}
+
+impl ChunkStorage for SqliteStorage {
+    fn load_chunk(&self, pos: ChunkPos) -> io::Result<Option<Vec<u8>>> {
+        self.reader
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT `data` FROM `chunks` WHERE `x` = ?1 AND `y` = ?2 AND `z` = ?3",
+                (pos.x, pos.y, pos.z),
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn load_metadata(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        self.reader
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT `value` FROM `metadata` WHERE `key` = ?1",
+                [key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn save(&self, batch: &[StorageWrite]) -> io::Result<()> {
+        let mut connection = self.writer.lock().unwrap();
+        let transaction = connection.transaction().map_err(sqlite_error)?;
+
+        {
+            let mut chunk_statement = transaction
+                .prepare_cached(
+                    "INSERT INTO `chunks` (`x`, `y`, `z`, `data`) VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT (`x`, `y`, `z`) DO UPDATE SET `data` = `excluded`.`data`",
+                )
+                .map_err(sqlite_error)?;
+            let mut metadata_statement = transaction
+                .prepare_cached(
+                    "INSERT INTO `metadata` (`key`, `value`) VALUES (?1, ?2)
+                    ON CONFLICT (`key`) DO UPDATE SET `value` = `excluded`.`value`",
+                )
+                .map_err(sqlite_error)?;
+
+            for write in batch {
+                match write {
+                    StorageWrite::Chunk { pos, data } => {
+                        chunk_statement.execute((pos.x, pos.y, pos.z, &data[..]))
+                    }
+                    StorageWrite::Metadata { key, value } => {
+                        metadata_statement.execute((key, value))
+                    }
+                }
+                .map_err(sqlite_error)?;
+            }
+        }
+
+        transaction.commit().map_err(sqlite_error)
+    }
+
+    fn chunk_positions(&self) -> io::Result<Vec<ChunkPos>> {
+        let connection = self.reader.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT `x`, `y`, `z` FROM `chunks`")
+            .map_err(sqlite_error)?;
+        let positions = statement
+            .query_map((), |row| {
+                Ok(ChunkPos::new(row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(sqlite_error)?;
+        positions
+            .collect::<rusqlite::Result<_>>()
+            .map_err(sqlite_error)
+    }
+
+    fn delete_chunks(&self, positions: &[ChunkPos]) -> io::Result<()> {
+        let mut connection = self.writer.lock().unwrap();
+        let transaction = connection.transaction().map_err(sqlite_error)?;
+
+        {
+            let mut statement = transaction
+                .prepare_cached("DELETE FROM `chunks` WHERE `x` = ?1 AND `y` = ?2 AND `z` = ?3")
+                .map_err(sqlite_error)?;
+
+            for pos in positions {
+                statement
+                    .execute((pos.x, pos.y, pos.z))
+                    .map_err(sqlite_error)?;
+            }
+        }
+
+        transaction.commit().map_err(sqlite_error)
+    }
+
+    fn compact(&self) -> io::Result<()> {
+        let connection = self.writer.lock().unwrap();
+        connection
+            .execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(sqlite_error)
+    }
+}
+
+fn sqlite_error(err: rusqlite::Error) -> io::Error {
+    io::Error::other(err)
+}
+
+/// Opens a world database, upgrading its schema if it was written by an older version.
+pub fn open_database(path: impl AsRef<Path>) -> rusqlite::Result<Connection> {
+    let connection = Connection::open(path)?;
+    connection
+        .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    connection.pragma_update(None, "synchronous", "NORMAL")?;
+    migrate(&connection)?;
+    Ok(connection)
+}
+
+fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+    let version: i32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    if version < 1 {
+        // The first schema had no key, so a position could have several rows of which the
+        // latest is current.
+        migrate_step(
+            connection,
+            1,
+            "CREATE TABLE IF NOT EXISTS `chunks` (
+                `x` INTEGER,
+                `y` INTEGER,
+                `z` INTEGER,
+                `data` BLOB
+            );
+            CREATE TABLE `chunks_v1` (
+                `x` INTEGER NOT NULL,
+                `y` INTEGER NOT NULL,
+                `z` INTEGER NOT NULL,
+                `data` BLOB NOT NULL,
+                PRIMARY KEY (`x`, `y`, `z`)
+            ) WITHOUT ROWID;
+            INSERT OR REPLACE INTO `chunks_v1`
+                SELECT `x`, `y`, `z`, `data` FROM `chunks` ORDER BY `rowid`;
+            DROP TABLE `chunks`;
+            ALTER TABLE `chunks_v1` RENAME TO `chunks`;",
+        )?;
+    }
+
+    if version < 2 {
+        migrate_step(
+            connection,
+            2,
+            "CREATE TABLE IF NOT EXISTS `metadata` (
+                `key` TEXT NOT NULL PRIMARY KEY,
+                `value` BLOB NOT NULL
+            );",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Runs one upgrade and records the version it brings the schema to in the same transaction, so
+/// a crash leaves the database at either the old version or the new one.
+fn migrate_step(connection: &Connection, version: i32, sql: &str) -> rusqlite::Result<()> {
+    let transaction = connection.unchecked_transaction()?;
+    transaction.execute_batch(sql)?;
+    transaction.pragma_update(None, "user_version", version)?;
+    transaction.commit()
+}
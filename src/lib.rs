@@ -0,0 +1,68 @@
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+use std::sync::Arc;
+
+use bevy::{prelude::*, utils::HashMap};
+use noise::Perlin;
+
+use block::{dirt::render_dirt, Block};
+use block_registry::SharedBlockRegistry;
+use config::Config;
+use level::{ChunkStorage, Level, RegionStorage, SqliteStorage, StorageBackend, WorldWriter};
+
+pub mod block;
+pub mod block_registry;
+pub mod config;
+pub mod console;
+pub mod edit;
+pub mod level;
+pub mod net;
+pub mod overlay;
+pub mod player;
+pub mod position;
+pub mod sky;
+
+#[cfg(test)]
+mod test_utils;
+
+const WORLD_PATH: &str = "chunks.sqlite";
+const REGION_PATH: &str = "regions";
+
+pub const GRAVITY: Vec3 = Vec3::new(0.0, -9.81 * 2.5, 0.0);
+
+#[derive(Resource, Default)]
+pub struct ChunkMaterial {
+    pub handle: Handle<StandardMaterial>,
+}
+
+pub fn setup_level(mut commands: Commands, config: Res<Config>) {
+    let storage: Arc<dyn ChunkStorage> = match config.storage_backend {
+        StorageBackend::Sqlite => Arc::new(SqliteStorage::open(WORLD_PATH).unwrap()),
+        StorageBackend::Region => Arc::new(RegionStorage::open(REGION_PATH).unwrap()),
+    };
+    let writer = WorldWriter::spawn(Arc::clone(&storage));
+
+    let seed = match storage.load_metadata("seed").unwrap() {
+        Some(bytes) => u32::from_be_bytes(bytes.try_into().expect("corrupt seed")),
+        None => {
+            writer.write_metadata("seed", Perlin::DEFAULT_SEED.to_be_bytes().to_vec());
+            Perlin::DEFAULT_SEED
+        }
+    };
+
+    commands.insert_resource(Level {
+        storage,
+        writer,
+        loaded_chunks: HashMap::new(),
+        noise: Perlin::new(seed),
+    });
+}
+
+pub fn register_blocks(registry: Res<SharedBlockRegistry>) {
+    registry.write().unwrap().register(
+        "dirt".to_string(),
+        Block {
+            render: render_dirt,
+        },
+    );
+}
@@ -1,35 +1,24 @@
 #![allow(clippy::type_complexity)]
 
-use std::{
-    f32::consts::FRAC_PI_2,
-    sync::{Arc, Mutex},
-};
+use std::f32::consts::FRAC_PI_2;
 
-use bevy::{core_pipeline::experimental::taa::TemporalAntiAliasPlugin, prelude::*, utils::HashMap};
+use bevy::{core_pipeline::experimental::taa::TemporalAntiAliasPlugin, prelude::*};
 use bevy_fps_counter::FpsCounterPlugin;
 use bevy_rapier3d::prelude::*;
 
-use block::{dirt::render_dirt, Block};
-use block_registry::SharedBlockRegistry;
-use config::ConfigPlugin;
-use level::{Level, LevelGenPlugin};
-use noise::Perlin;
-use overlay::OverlayPlugin;
-use player::PlayerPlugin;
-use rusqlite::Connection;
-
-mod block;
-mod block_registry;
-mod config;
-mod level;
-mod overlay;
-mod player;
-mod position;
-
-#[derive(Resource, Default)]
-pub struct ChunkMaterial {
-    pub handle: Handle<StandardMaterial>,
-}
+use game::{
+    block_registry::SharedBlockRegistry,
+    config::ConfigPlugin,
+    console::ConsolePlugin,
+    edit::EditPlugin,
+    level::LevelGenPlugin,
+    net::ClientPlugin,
+    overlay::OverlayPlugin,
+    player::PlayerPlugin,
+    register_blocks, setup_level,
+    sky::{SkyPlugin, Sun},
+    ChunkMaterial, GRAVITY,
+};
 
 fn main() {
     App::new()
@@ -41,7 +30,7 @@ fn main() {
             ..default()
         })
         .insert_resource(RapierConfiguration {
-            gravity: Vec3::Y * -9.81 * 2.5,
+            gravity: GRAVITY,
             ..default()
         })
         .add_plugins(
@@ -61,7 +50,11 @@ fn main() {
         .add_plugins(ConfigPlugin)
         .add_plugins(PlayerPlugin)
         .add_plugins(OverlayPlugin)
+        .add_plugins(EditPlugin)
+        .add_plugins(ConsolePlugin)
+        .add_plugins(SkyPlugin)
         .add_plugins(LevelGenPlugin)
+        .add_plugins(ClientPlugin)
         .add_systems(
             Startup,
             (setup_handles, setup_level, setup_world, register_blocks),
@@ -86,30 +79,8 @@ fn setup_handles(
     chunk_material.handle = materials.add(material);
 }
 
-fn setup_level(mut commands: Commands) {
-    let connection = Connection::open("chunks.sqlite").unwrap();
-
-    connection
-        .execute(
-            "CREATE TABLE IF NOT EXISTS `chunks` (
-        `x` INTEGER,
-        `y` INTEGER,
-        `z` INTEGER,
-        `data` BLOB
-    )",
-            (),
-        )
-        .unwrap();
-
-    commands.insert_resource(Level {
-        connection: Arc::new(Mutex::new(connection)),
-        loaded_chunks: HashMap::new(),
-        noise: Perlin::default(),
-    });
-}
-
 fn setup_world(mut commands: Commands) {
-    commands.spawn(DirectionalLightBundle {
+    commands.spawn(Sun).insert(DirectionalLightBundle {
         directional_light: DirectionalLight {
             shadows_enabled: false,
             illuminance: 15000.0,
@@ -123,12 +94,3 @@ fn setup_world(mut commands: Commands) {
         ..default()
     });
 }
-
-fn register_blocks(registry: Res<SharedBlockRegistry>) {
-    registry.write().unwrap().register(
-        "dirt".to_string(),
-        Block {
-            render: render_dirt,
-        },
-    );
-}
@@ -0,0 +1,326 @@
+mod client;
+mod connection;
+mod interpolation;
+mod protocol;
+mod remote_player;
+mod server;
+
+pub use client::*;
+pub use connection::*;
+pub use interpolation::*;
+pub use protocol::*;
+pub use remote_player::*;
+pub use server::*;
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        net::TcpStream,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use bevy::prelude::*;
+
+    use crate::{
+        block_registry::SharedBlockRegistry,
+        config::{Config, NetworkConfig},
+        level::{Chunk, ChunkEntities, ChunkOffsets, Level},
+        position::{BlockPos, ChunkPos},
+        test_utils::{memory_level, test_registry},
+    };
+
+    use super::*;
+
+    /// Polls until `done` accepts what has arrived, failing the test after a few seconds.
+    fn wait_for<T>(mut poll: impl FnMut() -> Vec<T>, mut done: impl FnMut(&[T]) -> bool) -> Vec<T> {
+        let start = Instant::now();
+        let mut received = Vec::new();
+
+        while !done(&received) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            received.extend(poll());
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        received
+    }
+
+    #[test]
+    fn test_loopback() {
+        let mut server = NetServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+        let mut client = NetClient::connect(addr, "builder", 4).unwrap();
+
+        let events = wait_for(|| server.poll(), |events| !events.is_empty());
+        let ServerEvent::Connected {
+            player_id,
+            ref name,
+            view_distance: 4,
+        } = events[0]
+        else {
+            panic!("expected a connection but got {events:?}");
+        };
+        assert_eq!(name, "builder");
+
+        let data = vec![0, 0];
+        server.send(
+            player_id,
+            &ServerMessage::ChunkData {
+                pos: ChunkPos::new(1, 0, -1),
+                data: data.clone(),
+            },
+        );
+
+        let messages = wait_for(|| client.poll().unwrap(), |messages| messages.len() == 2);
+        assert!(matches!(messages[0], ServerMessage::Welcome { .. }));
+        assert_eq!(
+            messages[1],
+            ServerMessage::ChunkData {
+                pos: ChunkPos::new(1, 0, -1),
+                data
+            }
+        );
+        assert_eq!(client.player_id(), Some(player_id));
+
+        let set_block = ClientMessage::SetBlock {
+            pos: BlockPos::new(3, 4, 5),
+            block: Some("dirt".to_string()),
+        };
+        let position = ClientMessage::Position(PlayerState {
+            position: Vec3::ONE,
+            yaw: 0.0,
+            pitch: 0.0,
+        });
+        client.send(&set_block);
+
+        // Datagrams can be dropped even on loopback, so keep sending until one arrives.
+        let events = wait_for(
+            || {
+                client.send_unreliable(&position);
+                server.poll()
+            },
+            |events| {
+                events.iter().any(|event| {
+                    matches!(
+                        event,
+                        ServerEvent::Message {
+                            message: ClientMessage::Position(_),
+                            ..
+                        }
+                    )
+                })
+            },
+        );
+        assert_eq!(
+            events[0],
+            ServerEvent::Message {
+                player_id,
+                message: set_block
+            }
+        );
+
+        drop(client);
+        wait_for(
+            || server.poll(),
+            |events| events.contains(&ServerEvent::Disconnected { player_id }),
+        );
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let mut server = NetServer::bind("127.0.0.1:0").unwrap();
+        let mut stream = Connection::new(TcpStream::connect(server.local_addr()).unwrap()).unwrap();
+
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION + 1,
+            name: "old".to_string(),
+            view_distance: 4,
+        };
+        stream.send(&hello.encode());
+        stream.flush().unwrap();
+
+        let frames = wait_for(
+            || {
+                assert!(server.poll().is_empty());
+                match stream.receive() {
+                    Ok(frames) => frames,
+                    Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Vec::new(),
+                    Err(error) => panic!("{error}"),
+                }
+            },
+            |frames| !frames.is_empty(),
+        );
+
+        assert!(matches!(
+            ServerMessage::decode(&frames[0]).unwrap(),
+            ServerMessage::Reject { .. }
+        ));
+        assert_eq!(server.players().count(), 0);
+    }
+
+    #[test]
+    fn test_hello_with_messages() {
+        let mut server = NetServer::bind("127.0.0.1:0").unwrap();
+        let mut stream = Connection::new(TcpStream::connect(server.local_addr()).unwrap()).unwrap();
+
+        // Sent together, so the server reads them all while it is still waiting for the hello.
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: "eager".to_string(),
+            view_distance: -3,
+        };
+        let forget = ClientMessage::ForgetChunk(ChunkPos::new(1, 2, 3));
+        stream.send(&hello.encode());
+        stream.send(&forget.encode());
+        stream.flush().unwrap();
+
+        let events = wait_for(|| server.poll(), |events| events.len() == 2);
+        let ServerEvent::Connected {
+            player_id,
+            view_distance,
+            ..
+        } = events[0]
+        else {
+            panic!("expected a connection but got {events:?}");
+        };
+        assert_eq!(view_distance, 1);
+        assert_eq!(
+            events[1],
+            ServerEvent::Message {
+                player_id,
+                message: forget
+            }
+        );
+    }
+
+    #[test]
+    fn test_slow_reader() {
+        let mut server = NetServer::bind("127.0.0.1:0").unwrap();
+        let _client = NetClient::connect(server.local_addr(), "idle", 4).unwrap();
+
+        let events = wait_for(|| server.poll(), |events| !events.is_empty());
+        let ServerEvent::Connected { player_id, .. } = events[0] else {
+            panic!("expected a connection but got {events:?}");
+        };
+
+        // The client never reads, so the server gives up once too much is queued for it.
+        let message = ServerMessage::ChunkData {
+            pos: ChunkPos::new(0, 0, 0),
+            data: vec![0; MAX_MESSAGE_SIZE / 2],
+        };
+        wait_for(
+            || {
+                server.send(player_id, &message);
+                server.poll()
+            },
+            |events| events.contains(&ServerEvent::Disconnected { player_id }),
+        );
+    }
+
+    #[test]
+    fn test_spoofed_position() {
+        let mut level = memory_level();
+        let registry = SharedBlockRegistry::default();
+        *registry.write().unwrap() = test_registry(&["dirt"]);
+
+        // Far above where the server places new players.
+        let target = BlockPos::new(0, 100, 0);
+        let mut chunk = Chunk::default();
+        let (chunk_pos, (x, y, z)) = target.chunk_pos();
+        *chunk.block_mut(x, y, z) = Some(registry.read().unwrap().block_id("dirt"));
+        level.add_chunk(chunk_pos, chunk);
+
+        let mut app = App::new();
+        app.add_plugins(ServerPlugin)
+            .insert_resource(Config {
+                network: NetworkConfig {
+                    bind_address: "127.0.0.1:0".to_string(),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert_resource(level)
+            .insert_resource(registry)
+            .init_resource::<ChunkEntities>()
+            .init_resource::<ChunkOffsets>();
+        app.update();
+
+        let addr = app.world.resource::<NetServer>().local_addr();
+        let mut client = NetClient::connect(addr, "cheater", 4).unwrap();
+
+        let position = |app: &mut App| {
+            app.world
+                .query::<(&NetworkPlayer, &Transform)>()
+                .get_single(&app.world)
+                .map(|(_, transform)| transform.translation)
+                .ok()
+        };
+
+        // Datagrams are only sent once the client has been welcomed, which also tells it where
+        // the server has put it.
+        let messages = wait_for(
+            || {
+                app.update();
+                client.poll().unwrap()
+            },
+            |messages| {
+                messages
+                    .iter()
+                    .any(|message| matches!(message, ServerMessage::Teleport { .. }))
+            },
+        );
+        let start = position(&mut app).unwrap();
+        assert!(messages.contains(&ServerMessage::Teleport { position: start }));
+
+        // Claims to jump next to the block, which is only believed as far as the player can move.
+        let spoof = ClientMessage::Position(PlayerState {
+            position: Vec3::from(target) + Vec3::new(0.5, 1.5, 0.5),
+            yaw: 0.0,
+            pitch: 0.0,
+        });
+        let corrections = wait_for(
+            || {
+                client.send_unreliable(&spoof);
+                app.update();
+                client
+                    .poll()
+                    .unwrap()
+                    .into_iter()
+                    .filter_map(|message| match message {
+                        ServerMessage::Teleport { position } => Some(position),
+                        _ => None,
+                    })
+                    .collect()
+            },
+            |corrections| !corrections.is_empty(),
+        );
+        // The client is told where it really is.
+        assert_ne!(corrections[0], start);
+        assert!(corrections[0].distance(Vec3::from(target)) > 50.0);
+
+        client.send(&ClientMessage::SetBlock {
+            pos: target,
+            block: None,
+        });
+        wait_for(
+            || {
+                app.update();
+                client.poll().unwrap()
+            },
+            |messages| {
+                messages.contains(&ServerMessage::BlockChanged {
+                    pos: target,
+                    block: Some("dirt".to_string()),
+                })
+            },
+        );
+        assert!(app
+            .world
+            .resource::<Level>()
+            .block(target)
+            .unwrap()
+            .is_some());
+    }
+}
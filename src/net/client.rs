@@ -0,0 +1,402 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bevy::{
+    prelude::*,
+    tasks::AsyncComputeTaskPool,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier3d::prelude::RigidBody;
+
+use crate::{
+    block_registry::SharedBlockRegistry,
+    config::Config,
+    edit::{BlocksEdited, WorldEdit},
+    level::{
+        decompress_chunk, spawn_chunk, Chunk, ChunkEntities, Dirty, GenerateTask, RemoteLevel,
+    },
+    player::{PendingSpawn, Player, PlayerCamera},
+    position::ChunkPos,
+    ChunkMaterial,
+};
+
+use super::{
+    interpolate_remote_players, setup_remote_player_assets, spawn_remote_player, update_nameplates,
+    ClientMessage, Connection, PlayerId, PlayerState, RemotePlayerAssets, RemotePlayers,
+    ServerMessage, SnapshotBuffer, PROTOCOL_VERSION,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many position updates are sent per second.
+const POSITION_RATE: f64 = 20.0;
+
+/// How many times a chunk from the server can fail to load before giving up on the server.
+const MAX_CHUNK_FAILURES: u32 = 3;
+
+/// A connection to a [`super::NetServer`].
+#[derive(Resource)]
+pub struct NetClient {
+    connection: Connection,
+    udp: UdpSocket,
+    player_id: Option<PlayerId>,
+    token: Option<u64>,
+}
+
+impl NetClient {
+    /// Connects and sends the handshake. The server's answer arrives through [`NetClient::poll`].
+    pub fn connect(addr: SocketAddr, name: &str, view_distance: i32) -> io::Result<Self> {
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        let mut connection = Connection::new(stream)?;
+
+        let local: SocketAddr = if addr.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let udp = UdpSocket::bind(local)?;
+        udp.connect(addr)?;
+        udp.set_nonblocking(true)?;
+
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: name.to_string(),
+            view_distance,
+        };
+        connection.send(&hello.encode());
+        connection.flush()?;
+
+        Ok(Self {
+            connection,
+            udp,
+            player_id: None,
+            token: None,
+        })
+    }
+
+    /// The id the server assigned, once it has accepted the handshake.
+    pub fn player_id(&self) -> Option<PlayerId> {
+        self.player_id
+    }
+
+    /// Collects everything the server sent. Fails once the connection is lost.
+    pub fn poll(&mut self) -> io::Result<Vec<ServerMessage>> {
+        self.connection.flush()?;
+
+        let mut messages = self
+            .connection
+            .receive()?
+            .iter()
+            .map(|frame| ServerMessage::decode(frame))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        for message in &messages {
+            if let ServerMessage::Welcome { player_id, token } = message {
+                self.player_id = Some(*player_id);
+                self.token = Some(*token);
+            }
+        }
+
+        let mut buffer = [0; 512];
+
+        while let Ok(len) = self.udp.recv(&mut buffer) {
+            // Datagrams may be stale or mangled, in which case they are simply dropped.
+            if let Ok(message @ ServerMessage::PlayerState { .. }) =
+                ServerMessage::decode(&buffer[..len])
+            {
+                messages.push(message);
+            }
+        }
+
+        Ok(messages)
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) {
+        self.connection.send(&message.encode());
+        // Errors surface on the next poll.
+        self.connection.flush().ok();
+    }
+
+    /// Sends over UDP, which is only possible after the handshake.
+    pub fn send_unreliable(&mut self, message: &ClientMessage) {
+        let Some(token) = self.token else {
+            return;
+        };
+
+        let mut datagram = token.to_be_bytes().to_vec();
+        datagram.extend(message.encode());
+        self.udp.send(&datagram).ok();
+    }
+}
+
+pub struct ClientPlugin;
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RemotePlayers>()
+            .init_resource::<StreamedChunks>()
+            .init_resource::<FailedChunks>()
+            .add_event::<ChunkReceived>()
+            .add_systems(Startup, setup_remote_player_assets)
+            .add_systems(PostStartup, connect_to_server)
+            .add_systems(
+                Update,
+                (
+                    receive_messages,
+                    load_received_chunks,
+                    send_messages,
+                    interpolate_remote_players,
+                    update_nameplates,
+                )
+                    .chain()
+                    .distributive_run_if(resource_exists::<NetClient>()),
+            );
+    }
+}
+
+/// Chunks received from the server, so it can be told when they are unloaded again.
+#[derive(Resource, Default)]
+struct StreamedChunks(HashSet<ChunkPos>);
+
+/// Chunks from the server that couldn't be read, reported by their loading tasks.
+#[derive(Resource, Default)]
+struct FailedChunks {
+    reported: Arc<Mutex<Vec<ChunkPos>>>,
+    failures: HashMap<ChunkPos, u32>,
+}
+
+/// Chunk data from the server, handed from [`receive_messages`] to [`load_received_chunks`].
+#[derive(Event)]
+struct ChunkReceived {
+    pos: ChunkPos,
+    data: Vec<u8>,
+}
+
+fn connect_to_server(mut commands: Commands, config: Res<Config>) {
+    let Some(address) = &config.network.server_address else {
+        return;
+    };
+
+    let client = address
+        .to_socket_addrs()
+        .and_then(|mut addrs| addrs.next().ok_or_else(|| io::ErrorKind::NotFound.into()))
+        .and_then(|addr| {
+            NetClient::connect(addr, &config.network.player_name, config.render_distance)
+        });
+
+    match client {
+        Ok(client) => {
+            info!("Connected to {address}");
+            commands.insert_resource(client);
+            commands.insert_resource(RemoteLevel);
+        }
+        Err(error) => error!("Could not connect to {address}: {error}"),
+    }
+}
+
+fn receive_messages(
+    mut commands: Commands,
+    mut client: ResMut<NetClient>,
+    mut remote_players: ResMut<RemotePlayers>,
+    mut received_chunks: EventWriter<ChunkReceived>,
+    mut edit: WorldEdit,
+    time: Res<Time>,
+    registry: Res<SharedBlockRegistry>,
+    remote_player_assets: Option<Res<RemotePlayerAssets>>,
+    mut buffers: Query<&mut SnapshotBuffer>,
+    mut player: Query<(Entity, &mut Transform), With<Player>>,
+) {
+    let messages = match client.poll() {
+        Ok(messages) => messages,
+        Err(error) => {
+            error!("Lost connection to the server: {error}");
+            commands.remove_resource::<NetClient>();
+            return;
+        }
+    };
+
+    for message in messages {
+        match message {
+            ServerMessage::Welcome { player_id, .. } => {
+                info!("Joined the server as player {player_id}");
+            }
+            ServerMessage::Reject { reason } => {
+                error!("The server refused the connection: {reason}");
+                commands.remove_resource::<NetClient>();
+                return;
+            }
+            ServerMessage::ChunkData { pos, data } => {
+                received_chunks.send(ChunkReceived { pos, data });
+            }
+            ServerMessage::BlockChanged { pos, block } => {
+                let block = block.and_then(|name| {
+                    let id = registry.read().unwrap().get_block_id(&name);
+                    if id.is_none() {
+                        warn!("The server placed unknown block `{name}`");
+                    }
+                    id
+                });
+                edit.sync_blocks([(pos, block)]);
+            }
+            ServerMessage::PlayerJoined { player_id, name } => {
+                info!("{name} joined");
+
+                let entity = spawn_remote_player(
+                    &mut commands,
+                    remote_player_assets.as_deref(),
+                    player_id,
+                    name,
+                );
+                remote_players.0.insert(player_id, entity);
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                if let Some(entity) = remote_players.0.remove(&player_id) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            ServerMessage::PlayerState { player_id, state } => {
+                let Some(&entity) = remote_players.0.get(&player_id) else {
+                    continue;
+                };
+
+                if let Ok(mut buffer) = buffers.get_mut(entity) {
+                    buffer.push(time.elapsed_seconds_f64(), state);
+                }
+            }
+            ServerMessage::Teleport { position } => {
+                let Ok((entity, mut transform)) = player.get_single_mut() else {
+                    continue;
+                };
+
+                // Held like a new spawn, since the chunks there may not have arrived yet.
+                transform.translation = position;
+                commands
+                    .entity(entity)
+                    .insert(RigidBody::Fixed)
+                    .insert(PendingSpawn::at(position));
+            }
+        }
+    }
+}
+
+fn load_received_chunks(
+    mut commands: Commands,
+    mut received_chunks: ResMut<Events<ChunkReceived>>,
+    mut streamed: ResMut<StreamedChunks>,
+    mut failed: ResMut<FailedChunks>,
+    mut entities: ResMut<ChunkEntities>,
+    registry: Res<SharedBlockRegistry>,
+    chunk_material: Option<Res<ChunkMaterial>>,
+) {
+    let reported = std::mem::take(&mut *failed.reported.lock().unwrap());
+
+    // Chunks that can never be read, such as ones with blocks this client doesn't know, would
+    // otherwise be sent again forever.
+    for pos in reported {
+        let failures = failed.failures.entry(pos).or_default();
+        *failures += 1;
+
+        if *failures >= MAX_CHUNK_FAILURES {
+            error!("Disconnecting, since chunk {pos:?} from the server can't be read");
+            commands.remove_resource::<NetClient>();
+            return;
+        }
+    }
+
+    let thread_pool = AsyncComputeTaskPool::get();
+
+    // Drained rather than read, so the chunk data is moved into the tasks instead of copied.
+    for ChunkReceived { pos, data } in received_chunks.drain() {
+        let registry = Arc::clone(&registry);
+        let reported = Arc::clone(&failed.reported);
+        // A corrupt chunk is dropped, or keeps its old contents if it was already loaded. A new
+        // chunk is then forgotten, which has the server send it again.
+        let task = thread_pool.spawn(async move {
+            decompress_chunk(&data)
+                .and_then(|bytes| Chunk::deserialize(&bytes, &registry.read().unwrap()))
+                .map_err(|err| {
+                    warn!("Dropping corrupt chunk {pos:?} from the server: {err}");
+                    reported.lock().unwrap().push(pos);
+                })
+                .ok()
+        });
+
+        streamed.0.insert(pos);
+
+        // A chunk sent again replaces the old contents in place.
+        if let Some(entity) = entities.get(pos) {
+            commands
+                .entity(entity)
+                .insert(GenerateTask::new(task))
+                .insert(Dirty);
+        } else {
+            spawn_chunk(
+                &mut commands,
+                &mut entities,
+                pos,
+                chunk_material.as_deref(),
+                task,
+            );
+        }
+    }
+}
+
+fn send_messages(
+    mut client: ResMut<NetClient>,
+    mut streamed: ResMut<StreamedChunks>,
+    mut edits: EventReader<BlocksEdited>,
+    mut last_position: Local<f64>,
+    time: Res<Time>,
+    registry: Res<SharedBlockRegistry>,
+    entities: Res<ChunkEntities>,
+    player: Query<&Transform, With<Player>>,
+    camera: Query<&Transform, With<PlayerCamera>>,
+) {
+    {
+        let registry = registry.read().unwrap();
+
+        for BlocksEdited(transaction) in edits.iter() {
+            for change in transaction.changes() {
+                client.send(&ClientMessage::SetBlock {
+                    pos: change.pos,
+                    block: change.after.map(|id| registry.name(id).to_string()),
+                });
+            }
+        }
+    }
+
+    let unloaded = streamed
+        .0
+        .iter()
+        .filter(|pos| !entities.contains(**pos))
+        .copied()
+        .collect::<Vec<_>>();
+
+    for pos in unloaded {
+        streamed.0.remove(&pos);
+        client.send(&ClientMessage::ForgetChunk(pos));
+    }
+
+    let now = time.elapsed_seconds_f64();
+
+    if now - *last_position < 1.0 / POSITION_RATE {
+        return;
+    }
+
+    *last_position = now;
+
+    let (Ok(player), Ok(camera)) = (player.get_single(), camera.get_single()) else {
+        return;
+    };
+
+    let (yaw, pitch, _) = camera.rotation.to_euler(EulerRot::YXZ);
+    client.send_unreliable(&ClientMessage::Position(PlayerState {
+        position: player.translation,
+        yaw,
+        pitch,
+    }));
+}
@@ -0,0 +1,112 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+};
+
+use super::MAX_MESSAGE_SIZE;
+
+/// The most data queued for a peer before [`Connection::flush`] gives up on it, so one that
+/// stops reading can't grow it without bound.
+const MAX_OUTGOING: usize = 8 * MAX_MESSAGE_SIZE;
+
+/// A non-blocking TCP stream carrying length-prefixed frames.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            closed: false,
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Queues a frame, which is written by the next [`Connection::flush`].
+    pub fn send(&mut self, payload: &[u8]) {
+        self.outgoing.extend((payload.len() as u32).to_be_bytes());
+        self.outgoing.extend(payload);
+    }
+
+    /// Writes as much of the queued data as the socket accepts without blocking. Fails if more
+    /// than [`MAX_OUTGOING`] is still queued afterwards.
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+
+        if self.outgoing.len() > MAX_OUTGOING {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "peer stopped reading",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Reads everything available and returns the frames completed so far. Fails once the peer
+    /// has closed the connection and every frame it sent has been returned.
+    pub fn receive(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut buffer = [0; 16 * 1024];
+
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.incoming.extend(&buffer[..read]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut frames = Vec::new();
+        let mut start = 0;
+
+        while let Some(header) = self.incoming.get(start..start + 4) {
+            let len = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+
+            if len > MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "message too large",
+                ));
+            }
+
+            let Some(frame) = self.incoming.get(start + 4..start + 4 + len) else {
+                break;
+            };
+
+            frames.push(frame.to_vec());
+            start += 4 + len;
+        }
+
+        self.incoming.drain(..start);
+
+        if self.closed && frames.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(frames)
+    }
+}
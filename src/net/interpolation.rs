@@ -0,0 +1,95 @@
+use std::{collections::VecDeque, f32::consts::PI};
+
+use bevy::prelude::Component;
+
+use super::PlayerState;
+
+/// How far behind the newest snapshot remote players are shown, so there is usually a later
+/// snapshot to interpolate towards.
+pub const INTERPOLATION_DELAY: f64 = 0.1;
+
+const MAX_SNAPSHOTS: usize = 32;
+
+/// Recent states received for a remote player, keyed by the local time they arrived.
+#[derive(Component, Debug, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<(f64, PlayerState)>,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, time: f64, state: PlayerState) {
+        if self.snapshots.back().is_some_and(|last| last.0 > time) {
+            return;
+        }
+
+        self.snapshots.push_back((time, state));
+
+        while self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// The state at `time`, interpolated between the snapshots either side of it and clamped to
+    /// the oldest and newest ones.
+    pub fn sample(&self, time: f64) -> Option<PlayerState> {
+        let first = self.snapshots.front()?;
+
+        if time <= first.0 {
+            return Some(first.1);
+        }
+
+        for (a, b) in self.snapshots.iter().zip(self.snapshots.iter().skip(1)) {
+            if time <= b.0 {
+                let t = ((time - a.0) / (b.0 - a.0)) as f32;
+                return Some(lerp_state(a.1, b.1, t));
+            }
+        }
+
+        self.snapshots.back().map(|last| last.1)
+    }
+}
+
+fn lerp_state(a: PlayerState, b: PlayerState, t: f32) -> PlayerState {
+    PlayerState {
+        position: a.position.lerp(b.position, t),
+        yaw: lerp_angle(a.yaw, b.yaw, t),
+        pitch: a.pitch + (b.pitch - a.pitch) * t,
+    }
+}
+
+/// Interpolates along the shorter way round the circle.
+fn lerp_angle(a: f32, b: f32, t: f32) -> f32 {
+    let delta = (b - a + PI).rem_euclid(2.0 * PI) - PI;
+    a + delta * t
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let state = |x: f32, yaw: f32| PlayerState {
+            position: Vec3::new(x, 0.0, 0.0),
+            yaw,
+            pitch: 0.0,
+        };
+
+        let mut buffer = SnapshotBuffer::default();
+        assert_eq!(buffer.sample(0.0), None);
+
+        buffer.push(1.0, state(0.0, PI - 0.1));
+        buffer.push(2.0, state(10.0, -PI + 0.1));
+        // Out of order snapshots are dropped.
+        buffer.push(1.5, state(100.0, 0.0));
+
+        assert_eq!(buffer.sample(0.0), Some(state(0.0, PI - 0.1)));
+        assert_eq!(buffer.sample(3.0), Some(state(10.0, -PI + 0.1)));
+
+        let middle = buffer.sample(1.5).unwrap();
+        assert!((middle.position.x - 5.0).abs() < 1e-5);
+        assert!((middle.yaw.abs() - PI).abs() < 1e-5);
+    }
+}
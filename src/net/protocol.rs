@@ -0,0 +1,448 @@
+use std::io;
+
+use bevy::prelude::Vec3;
+
+use crate::position::{BlockPos, ChunkPos};
+
+/// Bumped whenever the encoding of any message changes.
+pub const PROTOCOL_VERSION: u16 = 3;
+
+/// Frames larger than this are treated as a protocol error.
+pub const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+pub type PlayerId = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerState {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Hello {
+        version: u16,
+        name: String,
+        view_distance: i32,
+    },
+    SetBlock {
+        pos: BlockPos,
+        block: Option<String>,
+    },
+    /// The client unloaded a chunk, so it has to be sent again when it comes back into view.
+    ForgetChunk(ChunkPos),
+    /// Sent over UDP.
+    Position(PlayerState),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Welcome {
+        player_id: PlayerId,
+        token: u64,
+    },
+    Reject {
+        reason: String,
+    },
+    /// A chunk in the `Chunk::serialize` format, wrapped by `level::compress_chunk`.
+    ChunkData {
+        pos: ChunkPos,
+        data: Vec<u8>,
+    },
+    BlockChanged {
+        pos: BlockPos,
+        block: Option<String>,
+    },
+    PlayerJoined {
+        player_id: PlayerId,
+        name: String,
+    },
+    PlayerLeft {
+        player_id: PlayerId,
+    },
+    /// Sent over UDP.
+    PlayerState {
+        player_id: PlayerId,
+        state: PlayerState,
+    },
+    /// Moves the receiving player to where the server has it, such as when it joins or moved
+    /// further than the server allows.
+    Teleport {
+        position: Vec3,
+    },
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+
+        match self {
+            ClientMessage::Hello {
+                version,
+                name,
+                view_distance,
+            } => {
+                writer.u8(0);
+                writer.u16(*version);
+                writer.string(name);
+                writer.i32(*view_distance);
+            }
+            ClientMessage::SetBlock { pos, block } => {
+                writer.u8(1);
+                writer.block_pos(*pos);
+                writer.block(block);
+            }
+            ClientMessage::ForgetChunk(pos) => {
+                writer.u8(2);
+                writer.chunk_pos(*pos);
+            }
+            ClientMessage::Position(state) => {
+                writer.u8(3);
+                writer.player_state(*state);
+            }
+        }
+
+        writer.0
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader::new(bytes);
+
+        let message = match reader.u8()? {
+            0 => ClientMessage::Hello {
+                version: reader.u16()?,
+                name: reader.string()?,
+                view_distance: reader.i32()?,
+            },
+            1 => ClientMessage::SetBlock {
+                pos: reader.block_pos()?,
+                block: reader.block()?,
+            },
+            2 => ClientMessage::ForgetChunk(reader.chunk_pos()?),
+            3 => ClientMessage::Position(reader.player_state()?),
+            _ => return Err(invalid("unknown client message")),
+        };
+
+        reader.finish(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+
+        match self {
+            ServerMessage::Welcome { player_id, token } => {
+                writer.u8(0);
+                writer.u32(*player_id);
+                writer.0.extend(token.to_be_bytes());
+            }
+            ServerMessage::Reject { reason } => {
+                writer.u8(1);
+                writer.string(reason);
+            }
+            ServerMessage::ChunkData { pos, data } => {
+                writer.u8(2);
+                writer.chunk_pos(*pos);
+                writer.u32(data.len() as u32);
+                writer.0.extend(data);
+            }
+            ServerMessage::BlockChanged { pos, block } => {
+                writer.u8(3);
+                writer.block_pos(*pos);
+                writer.block(block);
+            }
+            ServerMessage::PlayerJoined { player_id, name } => {
+                writer.u8(4);
+                writer.u32(*player_id);
+                writer.string(name);
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                writer.u8(5);
+                writer.u32(*player_id);
+            }
+            ServerMessage::PlayerState { player_id, state } => {
+                writer.u8(6);
+                writer.u32(*player_id);
+                writer.player_state(*state);
+            }
+            ServerMessage::Teleport { position } => {
+                writer.u8(7);
+                writer.vec3(*position);
+            }
+        }
+
+        writer.0
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader::new(bytes);
+
+        let message = match reader.u8()? {
+            0 => ServerMessage::Welcome {
+                player_id: reader.u32()?,
+                token: u64::from_be_bytes(reader.take(8)?.try_into().unwrap()),
+            },
+            1 => ServerMessage::Reject {
+                reason: reader.string()?,
+            },
+            2 => {
+                let pos = reader.chunk_pos()?;
+                let len = reader.u32()? as usize;
+                ServerMessage::ChunkData {
+                    pos,
+                    data: reader.take(len)?.to_vec(),
+                }
+            }
+            3 => ServerMessage::BlockChanged {
+                pos: reader.block_pos()?,
+                block: reader.block()?,
+            },
+            4 => ServerMessage::PlayerJoined {
+                player_id: reader.u32()?,
+                name: reader.string()?,
+            },
+            5 => ServerMessage::PlayerLeft {
+                player_id: reader.u32()?,
+            },
+            6 => ServerMessage::PlayerState {
+                player_id: reader.u32()?,
+                state: reader.player_state()?,
+            },
+            7 => ServerMessage::Teleport {
+                position: reader.vec3()?,
+            },
+            _ => return Err(invalid("unknown server message")),
+        };
+
+        reader.finish(message)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend(value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_be_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.0.extend(value.to_be_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend(value.to_be_bytes());
+    }
+
+    /// Writes at most 255 bytes of `value`, cut short on a character boundary.
+    fn string(&mut self, value: &str) {
+        let mut len = value.len().min(u8::MAX as usize);
+        while !value.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        self.u8(len as u8);
+        self.0.extend(&value.as_bytes()[..len]);
+    }
+
+    fn block(&mut self, block: &Option<String>) {
+        self.string(block.as_deref().unwrap_or_default());
+    }
+
+    fn block_pos(&mut self, pos: BlockPos) {
+        for value in [pos.x, pos.y, pos.z] {
+            self.i32(value);
+        }
+    }
+
+    fn chunk_pos(&mut self, pos: ChunkPos) {
+        for value in [pos.x, pos.y, pos.z] {
+            self.i32(value);
+        }
+    }
+
+    fn vec3(&mut self, value: Vec3) {
+        for value in [value.x, value.y, value.z] {
+            self.f32(value);
+        }
+    }
+
+    fn player_state(&mut self, state: PlayerState) {
+        self.vec3(state.position);
+        self.f32(state.yaw);
+        self.f32(state.pitch);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    i: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, i: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.i..self.i + len)
+            .ok_or_else(|| invalid("truncated message"))?;
+        self.i += len;
+        Ok(slice)
+    }
+
+    fn finish<T>(self, message: T) -> io::Result<T> {
+        if self.i == self.bytes.len() {
+            Ok(message)
+        } else {
+            Err(invalid("trailing bytes after message"))
+        }
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        let value = f32::from_be_bytes(self.take(4)?.try_into().unwrap());
+
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(invalid("non-finite number"))
+        }
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("invalid string"))
+    }
+
+    /// Blocks are sent by name, with the empty string standing for air.
+    fn block(&mut self) -> io::Result<Option<String>> {
+        let name = self.string()?;
+        Ok((!name.is_empty()).then_some(name))
+    }
+
+    fn block_pos(&mut self) -> io::Result<BlockPos> {
+        Ok(BlockPos::new(self.i32()?, self.i32()?, self.i32()?))
+    }
+
+    fn chunk_pos(&mut self) -> io::Result<ChunkPos> {
+        Ok(ChunkPos::new(self.i32()?, self.i32()?, self.i32()?))
+    }
+
+    fn vec3(&mut self) -> io::Result<Vec3> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn player_state(&mut self) -> io::Result<PlayerState> {
+        Ok(PlayerState {
+            position: self.vec3()?,
+            yaw: self.f32()?,
+            pitch: self.f32()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let state = PlayerState {
+            position: Vec3::new(1.0, -2.5, 3.0),
+            yaw: 0.5,
+            pitch: -0.25,
+        };
+
+        for message in [
+            ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: "builder".to_string(),
+                view_distance: 8,
+            },
+            ClientMessage::SetBlock {
+                pos: BlockPos::new(-1, 2, 3),
+                block: None,
+            },
+            ClientMessage::ForgetChunk(ChunkPos::new(4, -5, 6)),
+            ClientMessage::Position(state),
+        ] {
+            assert_eq!(ClientMessage::decode(&message.encode()).unwrap(), message);
+        }
+
+        for message in [
+            ServerMessage::Welcome {
+                player_id: 7,
+                token: u64::MAX,
+            },
+            ServerMessage::ChunkData {
+                pos: ChunkPos::new(1, 2, 3),
+                data: vec![1, 2, 3],
+            },
+            ServerMessage::BlockChanged {
+                pos: BlockPos::new(0, 0, 0),
+                block: Some("dirt".to_string()),
+            },
+            ServerMessage::PlayerState {
+                player_id: 7,
+                state,
+            },
+            ServerMessage::Teleport {
+                position: Vec3::new(-4.0, 20.5, 8.0),
+            },
+        ] {
+            assert_eq!(ServerMessage::decode(&message.encode()).unwrap(), message);
+        }
+
+        assert!(ClientMessage::decode(&[0, 0]).is_err());
+        assert!(ServerMessage::decode(&[9]).is_err());
+    }
+
+    #[test]
+    fn test_long_string() {
+        // 254 bytes followed by a two byte character, which doesn't fit.
+        let name = format!("{}é", "a".repeat(254));
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name,
+            view_distance: 8,
+        };
+
+        let ClientMessage::Hello { name, .. } = ClientMessage::decode(&hello.encode()).unwrap()
+        else {
+            panic!("expected a hello");
+        };
+        assert_eq!(name, "a".repeat(254));
+    }
+}
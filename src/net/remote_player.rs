@@ -0,0 +1,166 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use crate::player::PlayerCamera;
+
+use super::{PlayerId, SnapshotBuffer, INTERPOLATION_DELAY};
+
+const FONT_PATH: &str = "fonts/UbuntuMonoNerdFontCompleteMono.ttf";
+
+/// Matches the local player's collider, so other players are as solid as you are to them.
+const HALF_EXTENTS: Vec3 = Vec3::new(0.4, 0.8, 0.4);
+
+/// Where the nameplate floats, relative to the centre of the body.
+const NAMEPLATE_OFFSET: Vec3 = Vec3::new(0.0, 1.2, 0.0);
+
+/// Another player connected to the same server.
+#[derive(Component)]
+pub struct RemotePlayer {
+    pub player_id: PlayerId,
+    pub name: String,
+}
+
+#[derive(Resource, Default)]
+pub struct RemotePlayers(pub HashMap<PlayerId, Entity>);
+
+/// Shared handles for the model and nameplate of every remote player.
+#[derive(Resource)]
+pub struct RemotePlayerAssets {
+    body: Handle<Mesh>,
+    head: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    font: Handle<Font>,
+}
+
+/// A name label kept over the head of its player.
+#[derive(Component)]
+pub struct Nameplate(pub Entity);
+
+pub(super) fn setup_remote_player_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(RemotePlayerAssets {
+        body: meshes.add(shape::Box::new(0.8, 1.1, 0.8).into()),
+        head: meshes.add(shape::Cube::new(0.5).into()),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.3, 0.45, 0.8),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        font: asset_server.load(FONT_PATH),
+    });
+}
+
+/// Spawns a remote player, with a model and nameplate when the assets are available.
+pub fn spawn_remote_player(
+    commands: &mut Commands,
+    assets: Option<&RemotePlayerAssets>,
+    player_id: PlayerId,
+    name: String,
+) -> Entity {
+    let mut entity = commands.spawn(RemotePlayer {
+        player_id,
+        name: name.clone(),
+    });
+
+    entity
+        .insert(SnapshotBuffer::default())
+        .insert(TransformBundle::default())
+        .insert(VisibilityBundle::default())
+        .insert(RigidBody::KinematicPositionBased)
+        .insert(Collider::cuboid(
+            HALF_EXTENTS.x,
+            HALF_EXTENTS.y,
+            HALF_EXTENTS.z,
+        ));
+
+    let Some(assets) = assets else {
+        return entity.id();
+    };
+
+    entity.with_children(|commands| {
+        commands.spawn(PbrBundle {
+            mesh: assets.body.clone(),
+            material: assets.material.clone(),
+            transform: Transform::from_xyz(0.0, -0.25, 0.0),
+            ..default()
+        });
+        commands.spawn(PbrBundle {
+            mesh: assets.head.clone(),
+            material: assets.material.clone(),
+            transform: Transform::from_xyz(0.0, 0.55, 0.0),
+            ..default()
+        });
+    });
+
+    let player = entity.id();
+
+    commands.spawn(Nameplate(player)).insert(TextBundle {
+        text: Text::from_section(
+            name,
+            TextStyle {
+                font: assets.font.clone(),
+                font_size: 18.0,
+                color: Color::WHITE,
+            },
+        ),
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+        visibility: Visibility::Hidden,
+        ..default()
+    });
+
+    player
+}
+
+pub(super) fn interpolate_remote_players(
+    time: Res<Time>,
+    mut players: Query<(&SnapshotBuffer, &mut Transform), With<RemotePlayer>>,
+) {
+    let render_time = time.elapsed_seconds_f64() - INTERPOLATION_DELAY;
+
+    for (buffer, mut transform) in players.iter_mut() {
+        if let Some(state) = buffer.sample(render_time) {
+            transform.translation = state.position;
+            transform.rotation = Quat::from_rotation_y(state.yaw);
+        }
+    }
+}
+
+/// Keeps each nameplate centred over its player's head on screen, which always faces the camera.
+pub(super) fn update_nameplates(
+    mut commands: Commands,
+    camera: Query<(&Camera, &GlobalTransform), With<PlayerCamera>>,
+    players: Query<&GlobalTransform, With<RemotePlayer>>,
+    mut nameplates: Query<(Entity, &Nameplate, &Node, &mut Style, &mut Visibility)>,
+) {
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+
+    for (entity, nameplate, node, mut style, mut visibility) in nameplates.iter_mut() {
+        let Ok(player) = players.get(nameplate.0) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        let position =
+            camera.world_to_viewport(camera_transform, player.translation() + NAMEPLATE_OFFSET);
+
+        let Some(position) = position else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        let size = node.size();
+        style.left = Val::Px(position.x - size.x / 2.0);
+        style.top = Val::Px(position.y - size.y);
+        *visibility = Visibility::Inherited;
+    }
+}
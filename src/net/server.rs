@@ -0,0 +1,496 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io,
+    net::{SocketAddr, TcpListener, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    block_registry::SharedBlockRegistry,
+    config::Config,
+    edit::{BlocksEdited, EditHistory, WorldEdit},
+    level::{
+        compress_chunk, in_render_distance, ChunkLoader, ChunkOffsets, Level, RenderDistance,
+        MAX_RENDER_DISTANCE,
+    },
+    player::spawn_position,
+    position::{BlockPos, ChunkPos},
+};
+
+use super::{ClientMessage, Connection, PlayerId, ServerMessage, PROTOCOL_VERSION};
+
+/// How far from a player, in blocks, their edits are accepted.
+const REACH_DISTANCE: f32 = 8.0;
+
+/// How fast a player's reported position may move, in blocks per second. Faster than anyone can
+/// walk or fly, and than most falls.
+const MAX_PLAYER_SPEED: f32 = 40.0;
+
+/// The longest time between position updates that counts towards how far a player may move, so
+/// a client can't stay quiet to save up for a teleport.
+const MAX_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The most chunks sent to each player per tick, so joining doesn't stall the server.
+const CHUNKS_PER_TICK: usize = 8;
+
+/// How long a new connection has to say hello before it is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    Connected {
+        player_id: PlayerId,
+        name: String,
+        view_distance: i32,
+    },
+    Disconnected {
+        player_id: PlayerId,
+    },
+    Message {
+        player_id: PlayerId,
+        message: ClientMessage,
+    },
+}
+
+struct Client {
+    connection: Connection,
+    token: u64,
+    udp_addr: Option<SocketAddr>,
+}
+
+/// Accepts players over TCP, with a UDP socket on the same port for position updates.
+#[derive(Resource)]
+pub struct NetServer {
+    listener: TcpListener,
+    udp: UdpSocket,
+    next_id: PlayerId,
+    /// Connections yet to say hello, with when they were accepted.
+    pending: Vec<(Connection, Instant)>,
+    clients: HashMap<PlayerId, Client>,
+}
+
+impl NetServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        let udp = UdpSocket::bind(listener.local_addr()?)?;
+        udp.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            udp,
+            next_id: 1,
+            pending: Vec::new(),
+            clients: HashMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        self.clients.keys().copied()
+    }
+
+    /// Accepts new connections, completes handshakes and collects everything players sent.
+    pub fn poll(&mut self) -> Vec<ServerEvent> {
+        let mut events = Vec::new();
+
+        while let Ok((stream, _)) = self.listener.accept() {
+            if let Ok(connection) = Connection::new(stream) {
+                self.pending.push((connection, Instant::now()));
+            }
+        }
+
+        for (mut connection, accepted) in std::mem::take(&mut self.pending) {
+            let frames = match connection.receive() {
+                Ok(frames) if !frames.is_empty() => frames,
+                Ok(_) if accepted.elapsed() < HANDSHAKE_TIMEOUT => {
+                    self.pending.push((connection, accepted));
+                    continue;
+                }
+                _ => continue,
+            };
+
+            // Whatever the client sent straight after saying hello arrives in the same read.
+            let Ok(mut messages) = frames
+                .iter()
+                .map(|frame| ClientMessage::decode(frame))
+                .collect::<io::Result<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            let ClientMessage::Hello {
+                version,
+                name,
+                view_distance,
+            } = messages.remove(0)
+            else {
+                continue;
+            };
+
+            if version != PROTOCOL_VERSION {
+                let reason = format!(
+                    "The server uses protocol version {PROTOCOL_VERSION} but the client uses {version}"
+                );
+                connection.send(&ServerMessage::Reject { reason }.encode());
+                connection.flush().ok();
+                continue;
+            }
+
+            let player_id = self.next_id;
+            self.next_id += 1;
+
+            let token = RandomState::new().build_hasher().finish();
+            connection.send(&ServerMessage::Welcome { player_id, token }.encode());
+
+            self.clients.insert(
+                player_id,
+                Client {
+                    connection,
+                    token,
+                    udp_addr: None,
+                },
+            );
+            events.push(ServerEvent::Connected {
+                player_id,
+                name,
+                view_distance: view_distance.clamp(1, MAX_RENDER_DISTANCE),
+            });
+            events.extend(
+                messages
+                    .into_iter()
+                    .map(|message| ServerEvent::Message { player_id, message }),
+            );
+        }
+
+        let mut disconnected = Vec::new();
+
+        for (&player_id, client) in self.clients.iter_mut() {
+            let messages = client.connection.flush().and_then(|_| {
+                client
+                    .connection
+                    .receive()?
+                    .iter()
+                    .map(|frame| ClientMessage::decode(frame))
+                    .collect::<io::Result<Vec<_>>>()
+            });
+
+            match messages {
+                Ok(messages) => events.extend(
+                    messages
+                        .into_iter()
+                        .map(|message| ServerEvent::Message { player_id, message }),
+                ),
+                Err(_) => disconnected.push(player_id),
+            }
+        }
+
+        for player_id in disconnected {
+            self.clients.remove(&player_id);
+            events.push(ServerEvent::Disconnected { player_id });
+        }
+
+        let mut buffer = [0; 512];
+
+        while let Ok((len, addr)) = self.udp.recv_from(&mut buffer) {
+            let Some((token, payload)) = buffer[..len].split_first_chunk::<8>() else {
+                continue;
+            };
+
+            let token = u64::from_be_bytes(*token);
+            let Some((&player_id, client)) = self
+                .clients
+                .iter_mut()
+                .find(|(_, client)| client.token == token)
+            else {
+                continue;
+            };
+
+            if let Ok(message @ ClientMessage::Position(_)) = ClientMessage::decode(payload) {
+                client.udp_addr = Some(addr);
+                events.push(ServerEvent::Message { player_id, message });
+            }
+        }
+
+        events
+    }
+
+    pub fn send(&mut self, player_id: PlayerId, message: &ServerMessage) {
+        if let Some(client) = self.clients.get_mut(&player_id) {
+            client.connection.send(&message.encode());
+            // Errors surface as a disconnection on the next poll.
+            client.connection.flush().ok();
+        }
+    }
+
+    /// Sends over UDP once the player's address is known from their own datagrams.
+    pub fn send_unreliable(&mut self, player_id: PlayerId, message: &ServerMessage) {
+        if let Some(addr) = self
+            .clients
+            .get(&player_id)
+            .and_then(|client| client.udp_addr)
+        {
+            self.udp.send_to(&message.encode(), addr).ok();
+        }
+    }
+
+    pub fn disconnect(&mut self, player_id: PlayerId) {
+        self.clients.remove(&player_id);
+    }
+}
+
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditHistory>()
+            .add_event::<BlocksEdited>()
+            .init_resource::<ConnectedPlayers>()
+            .add_systems(PostStartup, start_server)
+            .add_systems(
+                Update,
+                (receive_messages, send_chunks, broadcast_edits).chain(),
+            );
+    }
+}
+
+/// A connected player as seen by the server.
+#[derive(Component)]
+pub struct NetworkPlayer {
+    pub player_id: PlayerId,
+    pub name: String,
+    view_distance: i32,
+    sent_chunks: HashSet<ChunkPos>,
+    /// When the player's position was last updated, which limits how far the next update can
+    /// move it.
+    moved_at: Instant,
+}
+
+#[derive(Resource, Default)]
+pub struct ConnectedPlayers(pub HashMap<PlayerId, Entity>);
+
+fn start_server(mut commands: Commands, config: Res<Config>) {
+    let address = &config.network.bind_address;
+    let server = NetServer::bind(address)
+        .unwrap_or_else(|error| panic!("could not listen on {address}: {error}"));
+
+    info!("Listening on {}", server.local_addr());
+    commands.insert_resource(server);
+}
+
+fn receive_messages(
+    mut commands: Commands,
+    mut server: ResMut<NetServer>,
+    mut connected: ResMut<ConnectedPlayers>,
+    mut edit: WorldEdit,
+    registry: Res<SharedBlockRegistry>,
+    mut players: Query<(&mut NetworkPlayer, &mut Transform)>,
+    mut deferred: Local<Vec<ServerEvent>>,
+) {
+    let events = server.poll();
+
+    for event in std::mem::take(&mut *deferred).into_iter().chain(events) {
+        match event {
+            ServerEvent::Connected {
+                player_id,
+                name,
+                view_distance,
+            } => {
+                info!("{name} joined");
+
+                // The client can't know where the server will have it, so tell it.
+                let position = spawn_position(edit.level());
+                server.send(player_id, &ServerMessage::Teleport { position });
+
+                for (other, _) in players.iter() {
+                    let message = ServerMessage::PlayerJoined {
+                        player_id: other.player_id,
+                        name: other.name.clone(),
+                    };
+                    server.send(player_id, &message);
+                }
+
+                let message = ServerMessage::PlayerJoined {
+                    player_id,
+                    name: name.clone(),
+                };
+                for other in connected.0.keys().copied().collect::<Vec<_>>() {
+                    server.send(other, &message);
+                }
+
+                let entity = commands
+                    .spawn(NetworkPlayer {
+                        player_id,
+                        name,
+                        view_distance,
+                        sent_chunks: HashSet::new(),
+                        moved_at: Instant::now(),
+                    })
+                    .insert(ChunkLoader)
+                    .insert(TransformBundle::from(Transform::from_translation(position)))
+                    .id();
+                connected.0.insert(player_id, entity);
+            }
+            ServerEvent::Disconnected { player_id } => {
+                let Some(entity) = connected.0.remove(&player_id) else {
+                    continue;
+                };
+
+                if let Ok((player, _)) = players.get(entity) {
+                    info!("{} left", player.name);
+                }
+
+                commands.entity(entity).despawn_recursive();
+
+                for other in connected.0.keys().copied().collect::<Vec<_>>() {
+                    server.send(other, &ServerMessage::PlayerLeft { player_id });
+                }
+            }
+            ServerEvent::Message { player_id, message } => {
+                let Some(&entity) = connected.0.get(&player_id) else {
+                    continue;
+                };
+
+                // Players who joined this tick are only spawned once commands are applied, so
+                // what they sent along with their hello waits until the next tick.
+                let Ok((mut player, mut transform)) = players.get_mut(entity) else {
+                    deferred.push(ServerEvent::Message { player_id, message });
+                    continue;
+                };
+
+                match message {
+                    ClientMessage::SetBlock { pos, block } => {
+                        let block = match block {
+                            Some(name) => registry.read().unwrap().get_block_id(&name).map(Some),
+                            None => Some(None),
+                        };
+
+                        let center = Vec3::from(pos) + Vec3::splat(0.5);
+                        let in_reach = transform.translation.distance(center) <= REACH_DISTANCE;
+
+                        match (block, edit.level().block(pos)) {
+                            (Some(block), Some(_)) if in_reach => edit.set_block(pos, block),
+                            // Tell the player what is really there so they can undo their guess.
+                            (_, Some(current)) => {
+                                let registry = registry.read().unwrap();
+                                let message = ServerMessage::BlockChanged {
+                                    pos,
+                                    block: current.map(|id| registry.name(id).to_string()),
+                                };
+                                server.send(player_id, &message);
+                            }
+                            (_, None) => {}
+                        }
+                    }
+                    ClientMessage::ForgetChunk(pos) => {
+                        player.sent_chunks.remove(&pos);
+                    }
+                    ClientMessage::Position(mut state) => {
+                        // Reach is measured from here, so a client can't claim to be next to
+                        // whatever it wants to edit.
+                        let now = Instant::now();
+                        let elapsed = (now - player.moved_at).min(MAX_UPDATE_INTERVAL);
+                        let requested = state.position - transform.translation;
+                        let movement =
+                            requested.clamp_length_max(MAX_PLAYER_SPEED * elapsed.as_secs_f32());
+
+                        state.position = transform.translation + movement;
+                        transform.translation = state.position;
+                        player.moved_at = now;
+
+                        // Otherwise the client would carry on from somewhere the server doesn't
+                        // believe it is.
+                        if movement != requested {
+                            let position = state.position;
+                            server.send(player_id, &ServerMessage::Teleport { position });
+                        }
+
+                        let message = ServerMessage::PlayerState { player_id, state };
+                        for &other in connected.0.keys() {
+                            if other != player_id {
+                                server.send_unreliable(other, &message);
+                            }
+                        }
+                    }
+                    ClientMessage::Hello { .. } => {}
+                }
+            }
+        }
+    }
+}
+
+/// Streams loaded chunks to each player, nearest first.
+fn send_chunks(
+    mut server: ResMut<NetServer>,
+    config: Res<Config>,
+    level: Res<Level>,
+    registry: Res<SharedBlockRegistry>,
+    mut offsets: ResMut<ChunkOffsets>,
+    mut players: Query<(&mut NetworkPlayer, &Transform)>,
+) {
+    for (mut player, transform) in players.iter_mut() {
+        let distance = RenderDistance::new(
+            player.view_distance.min(config.render_distance),
+            player.view_distance.min(config.vertical_render_distance),
+        );
+        let center = BlockPos::from(transform.translation).chunk_pos().0;
+
+        player
+            .sent_chunks
+            .retain(|&pos| in_render_distance(center, pos, distance));
+
+        let mut budget = CHUNKS_PER_TICK;
+
+        for pos in offsets.get(distance).iter().map(|&offset| center + offset) {
+            if budget == 0 {
+                break;
+            }
+
+            if player.sent_chunks.contains(&pos) {
+                continue;
+            }
+
+            let Some(chunk) = level.chunk(pos) else {
+                continue;
+            };
+
+            let bytes = chunk.serialize(&registry.read().unwrap());
+            let data = compress_chunk(&bytes, config.chunk_compression);
+            server.send(player.player_id, &ServerMessage::ChunkData { pos, data });
+            player.sent_chunks.insert(pos);
+            budget -= 1;
+        }
+    }
+}
+
+fn broadcast_edits(
+    mut server: ResMut<NetServer>,
+    mut edits: EventReader<BlocksEdited>,
+    registry: Res<SharedBlockRegistry>,
+) {
+    let registry = registry.read().unwrap();
+    let players = server.players().collect::<Vec<_>>();
+
+    for BlocksEdited(transaction) in edits.iter() {
+        for change in transaction.changes() {
+            let message = ServerMessage::BlockChanged {
+                pos: change.pos,
+                block: change.after.map(|id| registry.name(id).to_string()),
+            };
+
+            for &player_id in &players {
+                server.send(player_id, &message);
+            }
+        }
+    }
+}
@@ -1,27 +1,103 @@
 use std::f32::consts::FRAC_PI_2;
 
 use bevy::{
+    app::AppExit,
     core_pipeline::{experimental::taa::TemporalAntiAliasBundle, tonemapping::Tonemapping},
-    ecs::event::ManualEventReader,
+    ecs::{event::ManualEventReader, query::Has},
     input::mouse::MouseMotion,
     pbr::ScreenSpaceAmbientOcclusionBundle,
     prelude::*,
     window::{CursorGrabMode, PrimaryWindow, Window},
 };
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     config::Config,
-    level::{Dirty, Level, CHUNK_SIZE},
+    edit::WorldEdit,
+    level::{
+        save_on_exit, terrain_height, ChunkEntities, ChunkLoader, ColliderLoader, EmptyCollider,
+        Level, RemoteLevel, CHUNK_SIZE,
+    },
     position::{BlockPos, ChunkPos},
 };
 
+mod controller;
+pub use controller::*;
+
+/// The world metadata key the local player is saved under.
+const SAVE_KEY: &str = "player";
+
+/// Half the height of the player's collider, from its centre to its feet.
+const HALF_HEIGHT: f32 = 0.8;
+
+/// Half the width of the player's collider.
+const HALF_WIDTH: f32 = 0.4;
+
 #[derive(Component)]
 pub struct Player;
 
 #[derive(Component)]
 pub struct PlayerCamera;
 
+/// Everything about the local player kept in the world save.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub velocity: Vec3,
+    pub game_mode: GameMode,
+}
+
+/// Holds the player in place until the chunks around `position` have loaded, with colliders, and
+/// a spot where it fits has been found.
+#[derive(Component)]
+pub struct PendingSpawn {
+    position: Vec3,
+    velocity: Vec3,
+    /// Whether to move the player up out of anything at `position` before releasing it, which
+    /// is only needed where it hasn't already been standing.
+    relocate: bool,
+}
+
+impl PendingSpawn {
+    /// Holds the player at `position`, at rest, until it can be placed there.
+    pub fn at(position: Vec3) -> Self {
+        Self {
+            position,
+            velocity: Vec3::ZERO,
+            relocate: true,
+        }
+    }
+}
+
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Survival,
+    /// Flies without gravity, but still collides with terrain.
+    Creative,
+    /// Flies without gravity and passes through terrain.
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Survival, GameMode::Creative, GameMode::Spectator];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+            GameMode::Spectator => "spectator",
+        }
+    }
+
+    pub fn is_flying(self) -> bool {
+        self != GameMode::Survival
+    }
+}
+
 #[derive(Resource, Default)]
 struct InputState {
     reader_motion: ManualEventReader<MouseMotion>,
@@ -33,27 +109,45 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<InputState>()
             .add_systems(Startup, (setup_player, setup_input))
+            .add_systems(PostStartup, restore_player)
+            .add_systems(Last, save_player.before(save_on_exit))
             .add_systems(
                 Update,
                 (
+                    place_player,
+                    hold_player,
                     toggle_grab,
                     player_look,
                     player_move,
+                    move_voxel_player.after(player_move),
+                    apply_game_mode,
+                    update_fog,
                     remove_block.after(apply_deferred),
                 ),
             );
     }
 }
 
-fn setup_player(mut commands: Commands) {
-    commands
-        .spawn(Player)
+fn setup_player(mut commands: Commands, config: Res<Config>) {
+    let mut player = commands.spawn(Player);
+
+    // Chunk colliders are needed around the player even while it is held in place.
+    if config.player_physics == PlayerPhysics::Voxel {
+        player.insert(VoxelController::default());
+    } else {
+        player.insert(ColliderLoader);
+    }
+
+    player
+        .insert(ChunkLoader)
         .insert(TransformBundle::default())
-        .insert(Collider::cuboid(0.4, 0.8, 0.4))
+        .insert(Collider::cuboid(HALF_WIDTH, HALF_HEIGHT, HALF_WIDTH))
         .insert(RigidBody::Dynamic)
         .insert(LockedAxes::ROTATION_LOCKED)
         .insert(Ccd::enabled())
         .insert(Velocity::default())
+        .insert(GravityScale(1.0))
+        .insert(GameMode::default())
         .insert(Transform::from_xyz(0.0, 20.0, 0.0))
         .insert(Friction::new(0.0))
         .with_children(|commands| {
@@ -62,10 +156,7 @@ fn setup_player(mut commands: Commands) {
                 .insert(ScreenSpaceAmbientOcclusionBundle::default())
                 .insert(TemporalAntiAliasBundle::default())
                 .insert(FogSettings {
-                    falloff: FogFalloff::Linear {
-                        start: (CHUNK_SIZE * 5) as f32,
-                        end: (CHUNK_SIZE * 7) as f32,
-                    },
+                    falloff: fog_falloff(config.render_distance),
                     ..default()
                 })
                 .insert(Camera3dBundle {
@@ -80,14 +171,256 @@ fn setup_player(mut commands: Commands) {
         });
 }
 
-fn remove_block(
-    mut level: ResMut<Level>,
+/// Fog that thickens over the last few chunks within `render_distance`, hiding where the loaded
+/// chunks end.
+fn fog_falloff(render_distance: i32) -> FogFalloff {
+    let chunk = CHUNK_SIZE as f32;
+
+    FogFalloff::Linear {
+        start: (render_distance - 3).max(0) as f32 * chunk,
+        end: (render_distance - 1).max(1) as f32 * chunk,
+    }
+}
+
+/// Moves the fog out with the render distance, so raising it shows the chunks beyond.
+fn update_fog(config: Res<Config>, mut fog: Query<&mut FogSettings, With<PlayerCamera>>) {
+    if !config.is_changed() {
+        return;
+    }
+
+    for mut fog in fog.iter_mut() {
+        fog.falloff = fog_falloff(config.render_distance);
+    }
+}
+
+fn restore_player(
     mut commands: Commands,
+    level: Res<Level>,
+    config: Res<Config>,
+    mut player: Query<(Entity, &mut Transform, &mut GameMode), With<Player>>,
+    mut camera: Query<&mut Transform, (With<PlayerCamera>, Without<Player>)>,
+) {
+    let (entity, mut transform, mut game_mode) = player.single_mut();
+
+    // A server decides where players are, and its world isn't the local save.
+    let save = if config.network.server_address.is_none() {
+        let data = level.storage.load_metadata(SAVE_KEY).unwrap_or_else(|err| {
+            warn!("Ignoring player save that couldn't be loaded: {err}");
+            None
+        });
+        data.and_then(|data| match ron::de::from_bytes::<PlayerSave>(&data) {
+            Ok(save) => Some(save),
+            Err(err) => {
+                warn!("Ignoring unreadable player save: {err}");
+                None
+            }
+        })
+    } else {
+        None
+    };
+
+    let pending = if let Some(save) = save {
+        camera.single_mut().rotation =
+            Quat::from_axis_angle(Vec3::Y, save.yaw) * Quat::from_axis_angle(Vec3::X, save.pitch);
+        *game_mode = save.game_mode;
+
+        PendingSpawn {
+            position: save.position,
+            velocity: save.velocity,
+            relocate: true,
+        }
+    } else {
+        PendingSpawn::at(spawn_position(&level))
+    };
+
+    transform.translation = pending.position;
+    commands
+        .entity(entity)
+        .insert(RigidBody::Fixed)
+        .insert(pending);
+}
+
+/// Where a player without a save starts, standing on the generated terrain at the origin.
+pub fn spawn_position(level: &Level) -> Vec3 {
+    let height = terrain_height(level.noise(), 0, 0);
+    Vec3::new(0.5, (height + 1) as f32 + HALF_HEIGHT, 0.5)
+}
+
+/// Releases the player once its spawn point is known to be free and the terrain around it is
+/// ready, moving it up out of anything built or generated there since it was saved. A player held
+/// where it was already standing is released in place.
+fn place_player(
+    mut commands: Commands,
+    level: Res<Level>,
+    entities: Res<ChunkEntities>,
+    chunks: Query<(Has<Collider>, Has<EmptyCollider>)>,
+    mut player: Query<(
+        Entity,
+        &PendingSpawn,
+        &mut Transform,
+        &mut Velocity,
+        Has<VoxelController>,
+    )>,
+) {
+    for (entity, pending, mut transform, mut velocity, voxel) in player.iter_mut() {
+        let position = if pending.relocate {
+            let feet = BlockPos::from((pending.position - Vec3::Y * HALF_HEIGHT).floor());
+            let Some(spawn) = find_spawn(&level, feet) else {
+                continue;
+            };
+
+            if spawn == feet {
+                pending.position
+            } else {
+                Vec3::from(spawn) + Vec3::new(0.5, HALF_HEIGHT + 0.01, 0.5)
+            }
+        } else {
+            pending.position
+        };
+
+        if !terrain_ready(&level, &entities, &chunks, position, !voxel) {
+            continue;
+        }
+
+        transform.translation = position;
+        velocity.linvel = pending.velocity;
+
+        let mut player = commands.entity(entity);
+        player.remove::<PendingSpawn>();
+
+        // A voxel controlled player is moved by hand, and its collider is only there for others.
+        if voxel {
+            player.remove::<RigidBody>();
+        } else {
+            player.insert(RigidBody::Dynamic);
+        }
+    }
+}
+
+/// Holds the player in place again when it reaches chunks that are still generating or have no
+/// collider yet, rather than letting it fall through them. A voxel controlled player already
+/// treats chunks that haven't loaded as solid, and a flying player can't fall.
+fn hold_player(
+    mut commands: Commands,
+    level: Res<Level>,
+    entities: Res<ChunkEntities>,
+    chunks: Query<(Has<Collider>, Has<EmptyCollider>)>,
+    player: Query<
+        (Entity, &Transform, &Velocity, &GameMode),
+        (
+            With<Player>,
+            Without<PendingSpawn>,
+            Without<VoxelController>,
+        ),
+    >,
+) {
+    for (entity, transform, velocity, game_mode) in player.iter() {
+        if game_mode.is_flying()
+            || terrain_ready(&level, &entities, &chunks, transform.translation, true)
+        {
+            continue;
+        }
+
+        // It was already standing here, so it is released in place once the terrain is ready.
+        commands
+            .entity(entity)
+            .insert(RigidBody::Fixed)
+            .insert(PendingSpawn {
+                position: transform.translation,
+                velocity: velocity.linvel,
+                relocate: false,
+            });
+    }
+}
+
+/// Whether every chunk within a block of the player at `position` has loaded and, if
+/// `colliders` is set, has had its collider built, even if it came out empty. A chunk keeps its
+/// old collider while an edit rebuilds it, so that doesn't count as missing.
+fn terrain_ready(
+    level: &Level,
+    entities: &ChunkEntities,
+    chunks: &Query<(Has<Collider>, Has<EmptyCollider>)>,
+    position: Vec3,
+    colliders: bool,
+) -> bool {
+    let reach = Vec3::new(HALF_WIDTH, HALF_HEIGHT, HALF_WIDTH) + 1.0;
+    let min = BlockPos::from((position - reach).floor()).chunk_pos().0;
+    let max = BlockPos::from((position + reach).floor()).chunk_pos().0;
+
+    (min.x..=max.x)
+        .flat_map(|x| (min.y..=max.y).map(move |y| (x, y)))
+        .flat_map(|(x, y)| (min.z..=max.z).map(move |z| ChunkPos::new(x, y, z)))
+        .all(|pos| {
+            if level.chunk(pos).is_none() {
+                return false;
+            }
+
+            if !colliders {
+                return true;
+            }
+
+            entities
+                .get(pos)
+                .and_then(|entity| chunks.get(entity).ok())
+                .is_some_and(|(collider, empty)| collider || empty)
+        })
+}
+
+/// The lowest position at or above `feet` where the player fits, or `None` if that can't be
+/// known until more chunks load.
+pub fn find_spawn(level: &Level, mut feet: BlockPos) -> Option<BlockPos> {
+    loop {
+        if level.block(feet)?.is_none() && level.block(feet + BlockPos::Y)?.is_none() {
+            return Some(feet);
+        }
+
+        feet += BlockPos::Y;
+    }
+}
+
+fn save_player(
+    mut exit: EventReader<AppExit>,
+    level: Res<Level>,
+    remote: Option<Res<RemoteLevel>>,
+    player: Query<(&Transform, &Velocity, &GameMode, Option<&PendingSpawn>), With<Player>>,
+    camera: Query<&Transform, With<PlayerCamera>>,
+) {
+    if exit.is_empty() {
+        return;
+    }
+
+    exit.clear();
+
+    let (Ok((transform, velocity, &game_mode, pending)), None) = (player.get_single(), remote)
+    else {
+        return;
+    };
+
+    // A held player is saved where it will be released.
+    let (position, velocity) = match pending {
+        Some(pending) => (pending.position, pending.velocity),
+        None => (transform.translation, velocity.linvel),
+    };
+
+    let (yaw, pitch, _) = camera.single().rotation.to_euler(EulerRot::YXZ);
+    let save = PlayerSave {
+        position,
+        yaw,
+        pitch,
+        velocity,
+        game_mode,
+    };
+
+    let data = ron::to_string(&save).unwrap();
+    level.writer.write_metadata(SAVE_KEY, data.into_bytes());
+}
+
+fn remove_block(
+    mut edit: WorldEdit,
     mut gizmos: Gizmos,
     primary_window: Query<&Window, With<PrimaryWindow>>,
     mouse: Res<Input<MouseButton>>,
     camera: Query<&GlobalTransform, With<PlayerCamera>>,
-    chunk_query: Query<(Entity, &ChunkPos)>,
 ) {
     let window = primary_window.single();
 
@@ -97,35 +430,14 @@ fn remove_block(
 
     let transform = camera.single();
 
-    if let Ok((x, y, z)) = raycast_blocks(&level, transform.translation(), transform.forward(), 6) {
+    if let Ok((x, y, z)) = raycast_blocks(
+        edit.level(),
+        transform.translation(),
+        transform.forward(),
+        6,
+    ) {
         if mouse.just_pressed(MouseButton::Left) {
-            let (chunk_pos, (rx, ry, rz)) = BlockPos::new(x, y, z).chunk_pos();
-            let Some(chunk) = level.chunk_mut(chunk_pos) else {
-                return;
-            };
-
-            *chunk.block_mut(rx, ry, rz) = None;
-
-            if let Some(entity) = chunk_query
-                .iter()
-                .find(|entity| entity.1 == &chunk_pos)
-                .map(|entity| entity.0)
-            {
-                commands.entity(entity).insert(Dirty);
-
-                for adjacent in [
-                    chunk_pos - ChunkPos::X,
-                    chunk_pos + ChunkPos::X,
-                    chunk_pos - ChunkPos::Y,
-                    chunk_pos + ChunkPos::Y,
-                    chunk_pos - ChunkPos::Z,
-                    chunk_pos + ChunkPos::Z,
-                ] {
-                    if let Some((entity, _)) = chunk_query.iter().find(|e| e.1 == &adjacent) {
-                        commands.entity(entity).insert(Dirty);
-                    }
-                }
-            }
+            edit.set_block(BlockPos::new(x, y, z), None);
         }
 
         gizmos.cuboid(
@@ -136,7 +448,7 @@ fn remove_block(
     }
 }
 
-fn raycast_blocks(
+pub fn raycast_blocks(
     level: &Level,
     start: Vec3,
     direction: Vec3,
@@ -223,11 +535,11 @@ fn player_move(
     keyboard: Res<Input<KeyCode>>,
     config: Res<Config>,
     camera: Query<&Transform, With<PlayerCamera>>,
-    mut player: Query<&mut Velocity, With<Player>>,
+    mut player: Query<(&mut Velocity, &GameMode), With<Player>>,
 ) {
     let window = primary_window.single();
     let transform = camera.single();
-    let mut velocity = player.single_mut();
+    let (mut velocity, game_mode) = player.single_mut();
 
     if window.cursor.grab_mode != CursorGrabMode::None {
         let local_z = transform.local_z();
@@ -252,7 +564,13 @@ fn player_move(
         velocity.linvel +=
             movement.normalize_or_zero() * time.delta_seconds() * config.movement_speed;
 
-        if keyboard.just_pressed(config.movement_controls.jump) {
+        if game_mode.is_flying() {
+            let up = Vec3::Y;
+            apply!(+= up if jump);
+            apply!(-= up if descend);
+
+            velocity.linvel.y += movement.y * time.delta_seconds() * config.movement_speed;
+        } else if keyboard.just_pressed(config.movement_controls.jump) {
             velocity.linvel.y = 9.0;
         }
     }
@@ -260,6 +578,25 @@ fn player_move(
     let slow_factor = (1.0 - time.delta_seconds() * 8.0).max(0.0);
     velocity.linvel.x *= slow_factor;
     velocity.linvel.z *= slow_factor;
+
+    if game_mode.is_flying() {
+        velocity.linvel.y *= slow_factor;
+    }
+}
+
+fn apply_game_mode(
+    mut commands: Commands,
+    mut player: Query<(Entity, &GameMode, &mut GravityScale), Changed<GameMode>>,
+) {
+    for (entity, &game_mode, mut gravity_scale) in player.iter_mut() {
+        gravity_scale.0 = if game_mode.is_flying() { 0.0 } else { 1.0 };
+
+        if game_mode == GameMode::Spectator {
+            commands.entity(entity).insert(ColliderDisabled);
+        } else {
+            commands.entity(entity).remove::<ColliderDisabled>();
+        }
+    }
 }
 
 fn setup_input(mut primary_window: Query<&mut Window, With<PrimaryWindow>>) {
@@ -283,12 +620,195 @@ fn toggle_grab(
     }
 }
 
-fn grab(window: &mut Window) {
+pub fn grab(window: &mut Window) {
     window.cursor.grab_mode = CursorGrabMode::Confined;
     window.cursor.visible = false;
 }
 
-fn ungrab(window: &mut Window) {
+pub fn ungrab(window: &mut Window) {
     window.cursor.grab_mode = CursorGrabMode::None;
     window.cursor.visible = true;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use bevy::tasks::{AsyncComputeTaskPool, TaskPool};
+
+    use crate::{
+        block_registry::SharedBlockRegistry,
+        level::{insert_colliders, update_colliders, Chunk, TaskStats},
+        position::ChunkPos,
+        test_utils::{memory_level, test_registry},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_find_spawn() {
+        let mut level = memory_level();
+        let registry = test_registry(&["dirt"]);
+        let dirt = Some(registry.block_id("dirt"));
+
+        let mut chunk = Chunk::default();
+        for y in 0..3 {
+            *chunk.block_mut(1, y, 1) = dirt;
+        }
+        // Only one block of headroom, which isn't enough.
+        *chunk.block_mut(1, 4, 1) = dirt;
+        level.add_chunk(ChunkPos::new(0, 0, 0), chunk);
+
+        assert_eq!(
+            find_spawn(&level, BlockPos::new(2, 0, 2)),
+            Some(BlockPos::new(2, 0, 2))
+        );
+        assert_eq!(
+            find_spawn(&level, BlockPos::new(1, 0, 1)),
+            Some(BlockPos::new(1, 5, 1))
+        );
+        assert_eq!(find_spawn(&level, BlockPos::new(1, 0, -1)), None);
+        assert_eq!(
+            find_spawn(&level, BlockPos::new(1, CHUNK_SIZE as i32 - 1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_held_in_place() {
+        let mut level = memory_level();
+        let registry = test_registry(&["dirt"]);
+        let dirt = registry.block_id("dirt");
+
+        let mut chunk = Chunk::default();
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    *chunk.block_mut(x, y, z) = Some(dirt);
+                }
+            }
+        }
+        let pos = ChunkPos::new(0, 0, 0);
+        level.add_chunk(pos, chunk);
+
+        let mut world = World::new();
+        let chunk_entity = world.spawn(pos).id();
+        let mut entities = ChunkEntities::default();
+        entities.insert(pos, chunk_entity);
+        world.insert_resource(level);
+        world.insert_resource(entities);
+
+        // Inside solid terrain, where there is nowhere to move either player up to.
+        let position = Vec3::splat(16.5);
+        let mut spawn = |game_mode: GameMode| {
+            world
+                .spawn((Player, Transform::from_translation(position)))
+                .insert((Velocity::default(), game_mode))
+                .id()
+        };
+        let walker = spawn(GameMode::Survival);
+        let flyer = spawn(GameMode::Spectator);
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems((hold_player, apply_deferred, place_player).chain());
+
+        // The chunk has no collider yet, but a flying player can't fall through it.
+        schedule.run(&mut world);
+        assert!(world.get::<PendingSpawn>(walker).is_some());
+        assert!(world.get::<PendingSpawn>(flyer).is_none());
+
+        world.entity_mut(chunk_entity).insert(EmptyCollider);
+        schedule.run(&mut world);
+        assert!(world.get::<PendingSpawn>(walker).is_none());
+        assert_eq!(
+            world.get::<Transform>(walker).unwrap().translation,
+            position
+        );
+    }
+
+    #[test]
+    fn test_held_once_next_to_empty_chunk() {
+        AsyncComputeTaskPool::init(TaskPool::default);
+
+        let mut level = memory_level();
+
+        let mut world = World::new();
+        world.init_resource::<SharedBlockRegistry>();
+        let dirt = {
+            let mut registry = world.resource::<SharedBlockRegistry>().write().unwrap();
+            *registry = test_registry(&["dirt"]);
+            registry.block_id("dirt")
+        };
+
+        // A floor at the edge of its chunk, next to a chunk that is all air.
+        let mut floor = Chunk::default();
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..2 {
+                    *floor.block_mut(x, y, z) = Some(dirt);
+                }
+            }
+        }
+
+        let mut entities = ChunkEntities::default();
+        for (pos, chunk) in [
+            (ChunkPos::new(0, 0, 0), floor),
+            (ChunkPos::new(1, 0, 0), Chunk::default()),
+        ] {
+            level.add_chunk(pos, chunk);
+            entities.insert(pos, world.spawn(pos).id());
+        }
+
+        world.insert_resource(level);
+        world.insert_resource(entities);
+        world.insert_resource(Config::default());
+        world.init_resource::<TaskStats>();
+
+        // Held from the start, as when restored.
+        let position = Vec3::new(CHUNK_SIZE as f32 - 0.5, 2.0 + HALF_HEIGHT, 16.5);
+        let transform = Transform::from_translation(position);
+        let player = world
+            .spawn((Player, transform, GlobalTransform::from(transform)))
+            .insert((Velocity::default(), RigidBody::Fixed, ColliderLoader))
+            .insert(PendingSpawn {
+                position,
+                velocity: Vec3::ZERO,
+                relocate: true,
+            })
+            .id();
+
+        let mut schedule = Schedule::default();
+        schedule.add_systems((
+            update_colliders,
+            insert_colliders,
+            place_player,
+            hold_player,
+        ));
+
+        let held = |world: &World| world.get::<PendingSpawn>(player).is_some();
+        let start = Instant::now();
+
+        // The colliders are built on the task pool, so wait for both to finish.
+        while held(&world) || world.resource::<TaskStats>().collider.completed < 2 {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            schedule.run(&mut world);
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Once released, the player stays released.
+        for _ in 0..10 {
+            schedule.run(&mut world);
+            assert!(!held(&world));
+        }
+
+        // Each collider was built once, including the empty one.
+        let air = world
+            .resource::<ChunkEntities>()
+            .get(ChunkPos::new(1, 0, 0));
+        assert!(world.get::<EmptyCollider>(air.unwrap()).is_some());
+        assert_eq!(world.resource::<TaskStats>().collider.completed, 2);
+    }
+}
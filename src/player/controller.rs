@@ -0,0 +1,309 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, level::Level, position::BlockPos, GRAVITY};
+
+use super::{GameMode, PendingSpawn, HALF_HEIGHT, HALF_WIDTH};
+
+/// How high the player climbs without jumping. Blocks are whole, so this is one block.
+const STEP_HEIGHT: f32 = 1.0;
+
+/// How far the player's box is kept from blocks it touches, so touching isn't overlapping.
+const SKIN: f32 = 1e-4;
+
+/// How much a sneaking move is shortened at a time until the player stays on the edge.
+const SNEAK_STEP: f32 = 0.05;
+
+/// The longest frame simulated at once, so a hitch doesn't move the player too far in one go.
+const MAX_DELTA: f32 = 0.1;
+
+/// How the local player moves and collides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerPhysics {
+    /// A Rapier rigid body colliding with the chunk colliders.
+    #[default]
+    Rapier,
+    /// Moved by [`move_voxel_player`] against the blocks themselves, so it never depends on
+    /// chunk colliders being built.
+    Voxel,
+}
+
+/// Marks a player moved by [`move_voxel_player`] instead of by Rapier.
+#[derive(Component, Default)]
+pub struct VoxelController {
+    pub on_ground: bool,
+}
+
+/// Moves voxel controlled players by their velocity, stopping at blocks, climbing single
+/// blocks and keeping sneaking players from walking off edges. Blocks in chunks that haven't
+/// loaded count as solid.
+pub fn move_voxel_player(
+    time: Res<Time>,
+    level: Res<Level>,
+    config: Res<Config>,
+    keyboard: Option<Res<Input<KeyCode>>>,
+    mut player: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut VoxelController,
+            &GameMode,
+            &GravityScale,
+        ),
+        Without<PendingSpawn>,
+    >,
+) {
+    let delta_seconds = time.delta_seconds().min(MAX_DELTA);
+
+    for (mut transform, mut velocity, mut controller, &game_mode, gravity_scale) in
+        player.iter_mut()
+    {
+        velocity.linvel += GRAVITY * gravity_scale.0 * delta_seconds;
+        let delta = velocity.linvel * delta_seconds;
+
+        if game_mode == GameMode::Spectator {
+            transform.translation += delta;
+            controller.on_ground = false;
+            continue;
+        }
+
+        let sneaking = !game_mode.is_flying()
+            && keyboard
+                .as_ref()
+                .is_some_and(|keyboard| keyboard.pressed(config.movement_controls.descend));
+
+        let movement = move_box(
+            &level,
+            transform.translation,
+            Vec3::new(HALF_WIDTH, HALF_HEIGHT, HALF_WIDTH),
+            delta,
+            sneaking,
+        );
+
+        transform.translation = movement.position;
+        controller.on_ground = movement.on_ground;
+
+        velocity.linvel = Vec3::select(movement.blocked, Vec3::ZERO, velocity.linvel);
+    }
+}
+
+struct Movement {
+    position: Vec3,
+    /// The axes the box was stopped on.
+    blocked: BVec3,
+    on_ground: bool,
+}
+
+/// Moves the box centred on `position` by up to `delta`, one axis at a time.
+fn move_box(
+    level: &Level,
+    position: Vec3,
+    half_size: Vec3,
+    delta: Vec3,
+    sneaking: bool,
+) -> Movement {
+    let mut bounds = Bounds {
+        min: position - half_size,
+        max: position + half_size,
+    };
+
+    let moved_y = bounds.sweep(level, 1, delta.y);
+    bounds.shift(1, moved_y);
+    let on_ground = delta.y < 0.0 && moved_y > delta.y;
+
+    let flat = bounds.move_horizontally(level, delta, on_ground && sneaking);
+    let mut blocked_x = flat.1 != delta.x;
+    let mut blocked_z = flat.2 != delta.z;
+    let mut result = flat.0;
+
+    // Blocked on the ground, so try again from a step higher and settle back down.
+    if on_ground && (blocked_x || blocked_z) {
+        let mut raised = bounds;
+        let climbed = raised.sweep(level, 1, STEP_HEIGHT);
+        raised.shift(1, climbed);
+
+        let (mut stepped, x, z) = raised.move_horizontally(level, delta, sneaking);
+        let settled = stepped.sweep(level, 1, -climbed);
+        stepped.shift(1, settled);
+
+        if x * x + z * z > flat.1 * flat.1 + flat.2 * flat.2 {
+            result = stepped;
+            blocked_x = x != delta.x;
+            blocked_z = z != delta.z;
+        }
+    }
+
+    Movement {
+        position: (result.min + result.max) / 2.0,
+        blocked: BVec3::new(blocked_x, moved_y != delta.y, blocked_z),
+        on_ground,
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Bounds {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Bounds {
+    fn shift(&mut self, axis: usize, distance: f32) {
+        self.min[axis] += distance;
+        self.max[axis] += distance;
+    }
+
+    /// Moves along x then z, returning the result and how far it went on each.
+    fn move_horizontally(self, level: &Level, delta: Vec3, sneaking: bool) -> (Bounds, f32, f32) {
+        let mut bounds = self;
+        let mut moved = [0.0; 3];
+
+        for axis in [0, 2] {
+            let mut distance = bounds.sweep(level, axis, delta[axis]);
+
+            // Shortened until the player still has something under it.
+            while sneaking && distance != 0.0 {
+                let mut next = bounds;
+                next.shift(axis, distance);
+
+                if next.supported(level) {
+                    break;
+                }
+
+                distance -= SNEAK_STEP.min(distance.abs()) * distance.signum();
+            }
+
+            bounds.shift(axis, distance);
+            moved[axis] = distance;
+        }
+
+        (bounds, moved[0], moved[2])
+    }
+
+    /// How far the box can move along `axis`, up to `distance`, before it hits a block.
+    fn sweep(&self, level: &Level, axis: usize, distance: f32) -> f32 {
+        if distance == 0.0 {
+            return 0.0;
+        }
+
+        let (a, b) = match axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        let range = |axis: usize| {
+            (self.min[axis] + SKIN).floor() as i32..=(self.max[axis] - SKIN).floor() as i32
+        };
+
+        let layer_solid = |layer: i32| {
+            range(a).any(|i| {
+                range(b).any(|j| {
+                    let mut pos = [0; 3];
+                    pos[axis] = layer;
+                    pos[a] = i;
+                    pos[b] = j;
+                    is_solid(level, BlockPos::new(pos[0], pos[1], pos[2]))
+                })
+            })
+        };
+
+        if distance > 0.0 {
+            let start = self.max[axis];
+            let end = start + distance;
+            let first = (start - SKIN).ceil() as i32;
+
+            for layer in first..end.ceil() as i32 {
+                if layer_solid(layer) {
+                    return (layer as f32 - start - SKIN).clamp(0.0, distance);
+                }
+            }
+        } else {
+            let start = self.min[axis];
+            let end = start + distance;
+            let first = (start + SKIN).floor() as i32 - 1;
+
+            for layer in (end.floor() as i32..=first).rev() {
+                if layer_solid(layer) {
+                    return ((layer + 1) as f32 - start + SKIN).clamp(distance, 0.0);
+                }
+            }
+        }
+
+        distance
+    }
+
+    /// Whether there is a block right under the box.
+    fn supported(&self, level: &Level) -> bool {
+        self.sweep(level, 1, -SNEAK_STEP) > -SNEAK_STEP
+    }
+}
+
+/// Whether a block stops the player. Unloaded blocks do, so the player can't fall into chunks
+/// that haven't loaded yet.
+fn is_solid(level: &Level, pos: BlockPos) -> bool {
+    level.block(pos).is_none_or(|block| block.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        level::Chunk,
+        position::ChunkPos,
+        test_utils::{memory_level, test_registry},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_move_box() {
+        let mut level = memory_level();
+        let registry = test_registry(&["dirt"]);
+        let dirt = Some(registry.block_id("dirt"));
+
+        // A floor at y = 0 from x = 0 to 9, with a single step at x = 5.
+        let mut chunk = Chunk::default();
+        for x in 0..10 {
+            for z in 0..10 {
+                *chunk.block_mut(x, 0, z) = dirt;
+            }
+        }
+        *chunk.block_mut(5, 1, 2) = dirt;
+        level.add_chunk(ChunkPos::new(0, 0, 0), chunk);
+
+        let half_size = Vec3::new(HALF_WIDTH, HALF_HEIGHT, HALF_WIDTH);
+
+        // Falls onto the floor and stops exactly on top of it.
+        let movement = move_box(
+            &level,
+            Vec3::new(2.5, 3.0, 2.5),
+            half_size,
+            -Vec3::Y * 5.0,
+            false,
+        );
+        assert!(movement.on_ground);
+        assert!(movement.blocked.y);
+        assert!((movement.position.y - (1.0 + HALF_HEIGHT)).abs() < 0.001);
+
+        let standing = movement.position;
+        let walk = |delta: Vec3, sneaking: bool| {
+            move_box(&level, standing, half_size, delta - Vec3::Y * 0.1, sneaking)
+        };
+
+        // Climbs onto the step rather than stopping at it.
+        let movement = walk(Vec3::X * 2.5, false);
+        assert!(!movement.blocked.x);
+        assert!((movement.position.y - (2.0 + HALF_HEIGHT)).abs() < 0.001);
+
+        // Stops where the loaded chunks end, even with nothing in the way.
+        let movement = walk(Vec3::NEG_X * 0.3 + Vec3::NEG_Z * 3.0, false);
+        assert!(movement.blocked.z);
+        assert!((movement.position.z - HALF_WIDTH).abs() < 0.01);
+
+        // Sneaking stops at the edge of the floor, walking doesn't.
+        let movement = walk(Vec3::Z * 8.0, true);
+        assert!(movement.position.z - HALF_WIDTH < 10.0);
+        assert!(movement.position.z - HALF_WIDTH > 9.9);
+        let movement = walk(Vec3::Z * 8.0, false);
+        assert!(movement.position.z - HALF_WIDTH > 10.0);
+    }
+}
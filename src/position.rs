@@ -130,6 +130,18 @@ impl ChunkPos {
         )
     }
 
+    /// The six chunks sharing a face with this one.
+    pub fn neighbours(self) -> [ChunkPos; 6] {
+        [
+            self - ChunkPos::X,
+            self + ChunkPos::X,
+            self - ChunkPos::Y,
+            self + ChunkPos::Y,
+            self - ChunkPos::Z,
+            self + ChunkPos::Z,
+        ]
+    }
+
     pub fn is_adjacent(self, pos: ChunkPos) -> bool {
         let is_left = pos == self - ChunkPos::X;
         let is_right = pos == self + ChunkPos::X;
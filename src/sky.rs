@@ -0,0 +1,69 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::prelude::*;
+
+use crate::config::Config;
+
+const NOON_ILLUMINANCE: f32 = 15000.0;
+const NOON_AMBIENT: f32 = 0.8;
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .add_systems(Update, (advance_time, update_sun).chain());
+    }
+}
+
+#[derive(Component)]
+pub struct Sun;
+
+/// The fraction of the day that has passed, where 0 is midnight and 0.5 is noon.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct TimeOfDay(pub f32);
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+impl TimeOfDay {
+    pub const NAMED: [(&'static str, f32); 4] = [
+        ("midnight", 0.0),
+        ("day", 0.3),
+        ("noon", 0.5),
+        ("night", 0.8),
+    ];
+
+    /// How high the sun is, from -1 at midnight to 1 at noon.
+    pub fn sun_height(self) -> f32 {
+        -(self.0 * TAU).cos()
+    }
+}
+
+fn advance_time(mut time_of_day: ResMut<TimeOfDay>, time: Res<Time>, config: Res<Config>) {
+    if config.daylight_cycle && config.day_length > 0.0 {
+        time_of_day.0 = (time_of_day.0 + time.delta_seconds() / config.day_length).fract();
+    }
+}
+
+fn update_sun(
+    time_of_day: Res<TimeOfDay>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut sun: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    if !time_of_day.is_changed() {
+        return;
+    }
+
+    let daylight = time_of_day.sun_height().max(0.0);
+
+    for (mut transform, mut light) in sun.iter_mut() {
+        transform.rotation = Quat::from_rotation_x(-FRAC_PI_2 + (time_of_day.0 - 0.5) * TAU);
+        light.illuminance = NOON_ILLUMINANCE * daylight;
+    }
+
+    ambient_light.brightness = NOON_AMBIENT * daylight.max(0.15);
+}
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use noise::Perlin;
+
+use crate::{
+    block::{dirt::render_dirt, Block},
+    block_registry::BlockRegistry,
+    level::{Level, MemoryStorage},
+};
+
+/// An empty level that is only saved to memory.
+pub fn memory_level() -> Level {
+    Level::new(Arc::new(MemoryStorage::default()), Perlin::default())
+}
+
+/// A registry with a block for each of `names`, all drawn like dirt.
+pub fn test_registry(names: &[&str]) -> BlockRegistry {
+    let mut registry = BlockRegistry::default();
+
+    for name in names {
+        registry.register(
+            name.to_string(),
+            Block {
+                render: render_dirt,
+            },
+        );
+    }
+
+    registry
+}